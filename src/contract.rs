@@ -1,18 +1,31 @@
 
+use std::collections::BTreeMap;
+
 use cosmwasm_std::{
-    to_binary, Api, Binary, Env, Extern, HandleResponse, InitResponse, Querier,
-    StdError, StdResult, Storage, Uint128, HumanAddr,Decimal
+    to_binary, from_binary, log, Api, Binary, Env, Extern, HandleResponse, InitResponse, Querier,
+    StdResult, Storage, Uint128, HumanAddr,Decimal
 };
 use secret_toolkit::snip721::{Metadata, Extension,Trait};
 use secret_toolkit::snip20::{register_receive_msg};
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaChaRng;
 use crate::rand::{sha_256, Prng};
-
-use crate::msg::{ HandleMsg, InitMsg, QueryMsg,Wallet, MetadataMsg};
-use crate::state::{config, config_read, State, store_members, read_members, store_user_info,read_user_info, save_metadata, read_metadata, save_rand, read_rand, read_url_info, store_url_info};
+use crate::merkle::{leaf_hash, verify_proof};
+use crate::error::ContractError;
+use crate::template;
+use crate::royalty::{self, royalty_info, validate_royalties};
+use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
+use crate::permit::{self, Permit, Permission};
+
+use crate::msg::{ HandleMsg, InitMsg, QueryMsg,Wallet, Collection, CollectionInfo, TokenMetadata, UploadProgress, UriPreview, MintMsg, MintedStatusResponse, Phase, Tier, TierInfo, UserInfoResponse, HandleAnswer, QueryWithPermit, SaleInfoResponse, ProvenanceResponse};
+use crate::state::{config, config_read, State, store_pool_index, read_pool_index, remove_pool_index, set_minted, is_minted, add_member, remove_member, is_member, read_member_position, read_member_at, read_member_count, store_user_info,read_user_info, RaffleResult, store_raffle, read_raffle, raffle_list, RAFFLE_PREFIX, store_token_metadata, read_token_metadata, store_upload_progress, read_upload_progress, clear_token_metadata, store_collections, read_collections, store_phases, read_phases, store_phase_mints, read_phase_mints, store_allocation_mints, read_allocation_mints, store_tiers, read_tiers, store_tier_mints, read_tier_mints, store_tier_total, read_tier_total, retire_tier, is_retired_tier, store_pending_rewards, read_pending_rewards, store_dust, read_dust, save_prng_seed, read_prng_seed, store_viewing_key, read_viewing_key, revoke_permit, is_permit_revoked, save_mint_seed, read_mint_seed, RevealInfo, save_reveal, read_reveal, save_entropy_pool, read_entropy_pool};
 use secret_toolkit::{snip20,snip721};
 pub const RESPONSE_BLOCK_SIZE: usize = 256;
+/// set_metadata messages sent by one Reveal or ContinueReveal
+pub const REVEAL_BATCH_SIZE: u32 = 50;
+pub const MAX_MINTED_STATUS_LIMIT: u32 = 1024;
+pub const DEFAULT_WHITE_USERS_LIMIT: u32 = 100;
+pub const MAX_WHITE_USERS_LIMIT: u32 = 500;
 
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
@@ -22,7 +35,7 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<InitResponse> {
     let state = State {
         count: Uint128(0),
-        total_supply:msg.total_supply,
+        total_supply:collections_supply(&msg.collections),
         admin: msg.admin,
         maximum_count : msg.maximum_count,
         public_price : msg.public_price,
@@ -34,21 +47,39 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         nft_contract_hash : "nft_hash".to_string(),
         token_address:msg.token_address,
         token_contract_hash:msg.token_contract_hash,
-        check_minted : msg.check_minted,
-        human_metadata:msg.human_metadata,
-        human_image:msg.human_image,
-        bull_image:msg.bull_image,
-        bull_metadata:msg.bull_metadata,
-        robot_image:msg.robot_image,
-        robot_metadata:msg.robot_metadata
+        merkle_root:None,
+        public_maximum_count:None,
+        wallet_maximum_count:None,
+        push_rewards:msg.push_rewards.unwrap_or(true),
+        dust_wallet:None,
+        contract_address:env.contract.address.clone(),
+        seed_commitment:msg.seed_commitment,
+        provenance_hash:msg.provenance_hash,
+        placeholder_uri:None,
+        sale_closed:false,
+        royalties:vec![]
     };
 
+    validate_collections(&msg.collections)?;
+    if state.provenance_hash.len() != 32{
+        return Err(ContractError::InvalidProvenanceHash {}.into());
+    }
+    // the admin is bound to the seed before any sale parameter is set
+    if state.seed_commitment.len() != 32{
+        return Err(ContractError::InvalidSeedCommitment {}.into());
+    }
+
     config(&mut deps.storage).save(&state)?;
-    store_members(&mut deps.storage).save(&msg.white_members)?;
-    let init_metadata:Vec<String> = vec![];
-    save_metadata(&mut deps.storage).save(&init_metadata)?;
+    save_prng_seed(&mut deps.storage).save(&sha_256(msg.prng_seed.as_slice()).to_vec())?;
+    save_entropy_pool(&mut deps.storage).save(&new_entropy(&env, msg.prng_seed.as_slice(), b"entropy_pool").to_vec())?;
+    store_collections(&mut deps.storage).save(&msg.collections)?;
+    store_phases(&mut deps.storage).save(&vec![])?;
+    store_tiers(&mut deps.storage).save(&vec![])?;
+    for member in msg.white_members.iter(){
+        add_member(&mut deps.storage, "", member)?;
+    }
 
-     let mut messages = vec![register_receive_msg(
+     let messages = vec![register_receive_msg(
         env.contract_code_hash,
         None,
         RESPONSE_BLOCK_SIZE,
@@ -66,22 +97,42 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
+    // every call, admin or not, makes the pool harder to predict
+    mix_entropy(&mut deps.storage, &env, &[])?;
     match msg {
         HandleMsg::Receive { sender,from,amount,msg} => mint_nft(deps,env,sender,from,amount,msg),
         HandleMsg::SetMaximumNft { amount } => set_maximum_nft(deps, env,amount),
-        HandleMsg::SetTotalSupply { amount } => set_total_supply(deps, env,amount),
         HandleMsg::ChangeAdmin { address } => set_admin(deps,env,address),
         HandleMsg::SetRewardWallet { wallet } => set_reward_wallet(deps,env,wallet),
         HandleMsg::SetPrice { public_price, private_price} =>set_price(deps,env,public_price,private_price),
         HandleMsg::SetSaleFlag { private_mint, public_mint }=> set_mint_time(deps,env,private_mint,public_mint),
-        HandleMsg::SetWhiteUsers { members } => set_white_members(deps,env,members),
-        HandleMsg::AddWhiteUser { member } => add_white_user(deps,env,member),
+        HandleMsg::AddWhiteUsers { members, tier } => add_white_users(deps,env,members,tier),
+        HandleMsg::RemoveWhiteUsers { members, tier } => remove_white_users(deps,env,members,tier),
         HandleMsg::SetNftAddress { nft_address,nft_contract_hash } => set_nft_address(deps,env,nft_address,nft_contract_hash),
         HandleMsg::SetTokenAddres{token_address,token_contract_hash} => set_token_address(deps,env,token_address,token_contract_hash),
-        HandleMsg::AddMetaData {key, metadata } => add_metadata(deps,env,key,metadata),
-        HandleMsg::SetMetaData { metadata }=> set_metadata(deps,env,metadata),
-        HandleMsg::SetRandom { }=> set_random(deps,env)
-    
+        HandleMsg::UploadTokenMetadata { collection, start, metadata } => upload_token_metadata(deps,env,collection,start,metadata),
+        HandleMsg::AddRaffleEntrants { raffle, entrants } => add_raffle_entrants(deps,env,raffle,entrants),
+        HandleMsg::DrawRaffle { raffle, winners } => draw_raffle(deps,env,raffle,winners),
+        HandleMsg::SetCollections { collections } => set_collections(deps,env,collections),
+        HandleMsg::AddCollection { collection } => add_collection(deps,env,collection),
+        HandleMsg::RemoveCollection { name } => remove_collection(deps,env,name),
+        HandleMsg::SetPhases { phases } => set_phases(deps,env,phases),
+        HandleMsg::SetMerkleRoot { root } => set_merkle_root(deps,env,root),
+        HandleMsg::SetTiers { tiers } => set_tiers(deps,env,tiers),
+        HandleMsg::SetWalletLimits { public_maximum_count, wallet_maximum_count } => set_wallet_limits(deps,env,public_maximum_count,wallet_maximum_count),
+        HandleMsg::SetRewardMode { push } => set_reward_mode(deps,env,push),
+        HandleMsg::ClaimRewards {  } => claim_rewards(deps,env),
+        HandleMsg::SetDustWallet { address } => set_dust_wallet(deps,env,address),
+        HandleMsg::SweepDust { recipient } => sweep_dust(deps,env,recipient),
+        HandleMsg::SetRoyalties { royalties } => set_royalties(deps,env,royalties),
+        HandleMsg::CreateViewingKey { entropy, .. } => create_viewing_key(deps,env,entropy),
+        HandleMsg::SetViewingKey { key, .. } => set_viewing_key(deps,env,key),
+        HandleMsg::RevokePermit { permit_name, .. } => revoke(deps,env,permit_name),
+        HandleMsg::RevealSeed { seed } => reveal_seed(deps,env,seed),
+        HandleMsg::SetPlaceholder { uri } => set_placeholder(deps,env,uri),
+        HandleMsg::CloseSale {  } => close_sale(deps,env),
+        HandleMsg::Reveal { seed } => reveal(deps,env,seed),
+        HandleMsg::ContinueReveal {  } => continue_reveal(deps,env)
     }
 }
 
@@ -91,151 +142,175 @@ pub fn mint_nft<S: Storage, A: Api, Q: Querier>(
     sender:HumanAddr,
     _from:HumanAddr,
     amount:Uint128,
-    _msg:Binary
+    msg:Binary
 ) -> StdResult<HandleResponse> {
     
     let state = config_read(&deps.storage).load()?;
-    if state.private_mint == false && state.public_mint ==false{
-        return Err(StdError::generic_err(
-            "PresaleNotStarted"
-        ))
+    if state.sale_closed{
+        return Err(ContractError::SaleClosed {}.into())
     }
+    let phase = match active_phase(&deps.storage, &state, env.block.time)?{
+        Some(phase) => phase,
+        None => return Err(ContractError::SaleNotStarted {}.into())
+    };
 
      if state.token_address != env.message.sender{
-        return Err(StdError::generic_err(
-            "Wrong contract address"
-        ))
+        return Err(ContractError::WrongContractAddress {}.into())
     }
 
-    if state.total_supply <= state.count{
-        return Err(StdError::generic_err(
-            "Can not mint any more"
-        ))
-    }    
+    let mint_msg = parse_mint_msg(&msg)?;
+    let quantity = mint_msg.quantity.unwrap_or(1);
+    if quantity == 0{
+        return Err(ContractError::InvalidQuantity {}.into())
+    }
 
-    let prng_seed: Vec<u8> = sha_256(base64::encode("entropy").as_bytes()).to_vec();
-    let random_seed = new_entropy(&env, prng_seed.as_ref(), prng_seed.as_ref());
-    let mut rng = ChaChaRng::from_seed(random_seed);
-    
-    let count = Uint128::u128(&state.total_supply);
-        
-    let mut rand_num = (rng.next_u32() % (count as u32)) as u16 ;
-    
-    let mut check = state.check_minted;
-   
-    while check[rand_num as usize]== false{
-        rand_num = (rand_num+1)%(count as u16);
+    if state.count + Uint128(quantity as u128) > state.total_supply{
+        return Err(ContractError::SoldOut { remaining: (state.total_supply - state.count)? }.into())
     }
-    check[rand_num as usize] = false;
-    
-    config(&mut deps.storage).update(|mut state| {
-        state.check_minted = check;
-        Ok(state)
-    })?;
 
-    config(&mut deps.storage).update(|mut state|{
-        state.count = state.count+Uint128(1);
-        Ok(state)
-    })?;
+    let mut allocation = None;
+    let mut eligible = !phase.tiers_only && (!phase.whitelist_only || is_member(&deps.storage, "", &sender));
+    if !eligible && !phase.tiers_only {
+        eligible = match (&state.merkle_root, &mint_msg.proof){
+            (Some(root), Some(proof)) => verify_proof(root.as_slice(), leaf_hash(sender.as_str(), mint_msg.allocation), proof),
+            _ => false
+        };
+        if eligible{
+            allocation = mint_msg.allocation;
+        }
+    }
 
-    let key =  rand_num/705;
-    let rand_num = rand_num%705;
-
-    let  token_url : String;
-    let  image_url :String;
-    let token_id:String;
-    //	SteamPunk_Human_70.json
-    if key == 0{
-        let id = [["SteamPunk_Human".to_string(),rand_num.to_string()].join("_"),"json".to_string()].join(".");
-        let image_id =  [["SteamPunk_Human".to_string(),rand_num.to_string()].join("_"),"png".to_string()].join(".");
-        token_url = [state.human_metadata,id].join("/");
-        image_url = [state.human_image,image_id].join("/");
-        token_id = ["SteamPunk_Human".to_string(),rand_num.to_string()].join(".");
-    }
-    else{
-        if key == 1{
-        let id = [["SteamPunk_Bull".to_string(),rand_num.to_string()].join("_"),"json".to_string()].join(".");
-        let image_id =  [["SteamPunk_Bull".to_string(),rand_num.to_string()].join("_"),"png".to_string()].join(".");
-        token_url = [state.bull_metadata,id].join("/");
-        image_url = [state.bull_image,image_id].join("/");
-        token_id = ["SteamPunk_Bull".to_string(),rand_num.to_string()].join(".");
-        }
-       else{
-           let id = [["SteamPunk_Robot".to_string(),rand_num.to_string()].join("_"),"json".to_string()].join(".");
-        let image_id =  [["SteamPunk_Robot".to_string(),rand_num.to_string()].join("_"),"png".to_string()].join(".");
-        token_url = [state.robot_metadata,id].join("/");
-        image_url = [state.robot_image,image_id].join("/");
-        token_id = ["SteamPunk_Robot".to_string(),rand_num.to_string()].join(".");
-        }
-}
-
-    if state.private_mint {
-        let members = read_members(&deps.storage).load()?;
-        let mut flag = false;
-        for member in members {
-            if member == sender {
-                flag = true;
-            }
+    // a tier is used when it is cheaper than the phase or the sender's only way in
+    let (tier, tier_room) = best_tier(&deps.storage, &phase, &sender, quantity)?;
+    let tier = tier.filter(|tier| !eligible || tier.price < phase.price);
+    if !eligible && tier.is_none(){
+        if let Some(room) = tier_room{
+            return Err(ContractError::MintLimitReached { remaining: Uint128(room as u128) }.into())
         }
-        if !flag{
-            return Err(StdError::generic_err(
-                "You are not whitelisted")
-            )
+        return Err(ContractError::NotWhitelisted {}.into())
+    }
+
+    let price = match &tier{
+        Some(tier) => tier.price,
+        None => phase.price
+    };
+    let expected = match price.u128().checked_mul(quantity as u128){
+        Some(expected) => Uint128(expected),
+        None => return Err(ContractError::PriceOverflow { price, quantity }.into())
+    };
+    if amount != expected{
+        return Err(ContractError::WrongAmount { expected, received: amount }.into())
+    }
+
+    let minted = read_phase_mints(&deps.storage, &phase.name, sender.as_str());
+    let phase_mints = minted + quantity;
+    if let Some(limit) = phase.per_wallet_limit{
+        if Uint128(phase_mints as u128) > limit{
+            return Err(ContractError::MintLimitReached { remaining: (limit - Uint128(minted as u128)).unwrap_or_default() }.into())
         }
-        if amount != state.private_price{
-            return Err(StdError::generic_err(
-                "Not exact money"
-            ))
+    }
+    // the allocation in the leaf is for the whole sale, not for each phase
+    if let Some(allocation) = allocation{
+        let allocation_mints = read_allocation_mints(&deps.storage, sender.as_str());
+        if allocation_mints + quantity > allocation{
+            return Err(ContractError::MintLimitReached { remaining: Uint128(allocation.saturating_sub(allocation_mints) as u128) }.into())
         }
-        let user_info = read_user_info(&deps.storage,&sender.as_str());
-        
-        
-        if user_info == None{
-            store_user_info(& mut deps.storage, &sender.as_str(), vec![token_id.clone()])?;
-        }
-        else {
-            let mut new_user_info = user_info.unwrap();
-            if Uint128(new_user_info.len() as u128) >= state.maximum_count{
-                return Err(StdError::generic_err(
-                    "You can not mint any more"
-                ))
-            }
-            new_user_info.push(token_id.clone());
-            store_user_info(& mut deps.storage, &sender.as_str(), new_user_info)?;
+        store_allocation_mints(&mut deps.storage, sender.as_str(), allocation_mints + quantity)?;
+    }
+    let mut user_info = read_user_info(&deps.storage,&sender.as_str()).unwrap_or_default();
+    if let Some(limit) = state.wallet_maximum_count{
+        if Uint128((user_info.len() + quantity as usize) as u128) > limit{
+            return Err(ContractError::MintLimitReached { remaining: (limit - Uint128(user_info.len() as u128)).unwrap_or_default() }.into())
         }
+    }
+    store_phase_mints(&mut deps.storage, &phase.name, sender.as_str(), phase_mints)?;
+    if let Some(tier) = &tier{
+        let tier_mints = read_tier_mints(&deps.storage, &tier.name, sender.as_str()) + quantity;
+        store_tier_mints(&mut deps.storage, &tier.name, sender.as_str(), tier_mints)?;
+        let tier_total = read_tier_total(&deps.storage, &tier.name) + quantity;
+        store_tier_total(&mut deps.storage, &tier.name, tier_total)?;
+    }
 
+    // pre-reveal tokens are numbered in mint order, the reveal offset shuffles them
+    let mut rng = match state.placeholder_uri{
+        Some(_) => None,
+        None => {
+            // the block and sender are known in advance; the seed and the
+            // minter's entropy are not
+            let mint_seed = match read_mint_seed(&deps.storage).may_load()?{
+                Some(seed) => seed,
+                None => return Err(ContractError::SeedNotRevealed {}.into())
+            };
+            let user_entropy = mint_msg.entropy.unwrap_or_default();
+            let pool_entropy = mix_entropy(&mut deps.storage, &env, user_entropy.as_bytes())?;
+            let random_seed = new_entropy(&env, &mint_seed, &pool_entropy);
+            Some(ChaChaRng::from_seed(random_seed))
+        }
+    };
     
+    let mut remaining = (state.total_supply.u128() - state.count.u128()) as u32;
+    let collections = read_collections(&deps.storage).load()?;
+    let mut code_rng = Prng::new(&mix_entropy(&mut deps.storage, &env, b"secret_code")?, &[]);
+    let royalty_info = royalty_info(&state.royalties);
+    let mut mints = vec![];
+
+    for i in 0..quantity{
+        let (token_id, metadata, private_metadata) = match &mut rng{
+            Some(rng) => {
+                let index = draw_index(&mut deps.storage, remaining, rng.next_u32())?;
+                remaining -= 1;
+                set_minted(&mut deps.storage, index)?;
+                let (token_id, metadata) = art_metadata(&deps.storage, &collections, index as u128)?;
+                (token_id, metadata, unlockable_metadata(&collections, index as u128, &mut code_rng)?)
+            },
+            None => {
+                let index = state.count.u128() as u32 + i;
+                set_minted(&mut deps.storage, index)?;
+                // the reveal waits for the sellout, so every pre-reveal mint gets the
+                // placeholder; the unlockables would give the art away
+                let (token_id, _, _) = token_info(&collections, index as u128)?;
+                (token_id, token_metadata(state.placeholder_uri.clone(), None), None)
+            }
+        };
+        user_info.push(token_id.clone());
+        mints.push(royalty::Mint{
+            token_id:Some(token_id),
+            owner:Some(sender.clone()),
+            public_metadata:Some(metadata),
+            private_metadata,
+            royalty_info:royalty_info.clone(),
+            memo:None
+        });
+    }
+
+    config(&mut deps.storage).update(|mut state| {
+        state.count = state.count+Uint128(quantity as u128);
+        Ok(state)
+    })?;
+    store_user_info(& mut deps.storage, &sender.as_str(), user_info)?;
 
-        let mut res = vec![
-           snip721::mint_nft_msg(Some(token_id),
-            Some(sender), 
-            Some(Metadata{
-                token_uri:None,
-                extension:Some(Extension{
-                    image:Some(image_url),
-                    image_data:None,
-                    external_url:Some(token_url),
-                    description:None,
-                    name:None,
-                    attributes:None,
-                    background_color:None,
-                    animation_url:None,
-                    youtube_url:None,
-                    media:None,
-                    protected_attributes:None
-                })
-            }),
-            None, None, None,
-            RESPONSE_BLOCK_SIZE, 
+    let mut res = vec![
+        royalty::batch_mint_nft_msg(mints,
+            None,
             state.nft_contract_hash, 
             state.nft_address
         )?
-        ];
-        
-        for reward_member in state.reward_wallet{
+    ];
+    
+    let (shares, dust) = split_rewards(&state.reward_wallet, amount, &state.dust_wallet)?;
+    if dust > Uint128(0){
+        let dust = read_dust(&deps.storage) + dust;
+        store_dust(&mut deps.storage, dust)?;
+    }
+    // shares are sent with the mint unless the sale credits them for ClaimRewards
+    for (address, share) in shares{
+        if share == Uint128(0){
+            continue;
+        }
+        if state.push_rewards{
             res.push(
-                snip20::transfer_msg(reward_member.address, 
-                    amount*reward_member.portion, 
+                snip20::transfer_msg(address, 
+                    share, 
                     None,
                     None,
                     RESPONSE_BLOCK_SIZE, 
@@ -243,703 +318,3000 @@ pub fn mint_nft<S: Storage, A: Api, Q: Querier>(
                     state.token_address.clone())?
             )
         }
-          Ok(HandleResponse {
-             messages:res,
-              log: vec![],
-             data: None,
-         })
+        else{
+            let pending = read_pending_rewards(&deps.storage, address.as_str()) + share;
+            store_pending_rewards(&mut deps.storage, address.as_str(), pending)?;
+        }
     }
-   
-    else {
-        if amount != state.public_price{
-            return Err(StdError::generic_err(
-                "Not exact money"
-            ))
+    Ok(HandleResponse {
+        messages:res,
+        log: vec![],
+        data: None,
+    })
+}
+
+/// Splits `amount` by the wallets' portions. Decimal multiplication truncates,
+/// so the shares can fall short of `amount`; the remainder goes to
+/// `dust_wallet` when one is set and is returned as dust otherwise.
+fn split_rewards(wallets: &[Wallet], amount: Uint128, dust_wallet: &Option<HumanAddr>) -> StdResult<(Vec<(HumanAddr, Uint128)>, Uint128)> {
+    let mut shares: Vec<(HumanAddr, Uint128)> = wallets.iter().map(|wallet| (wallet.address.clone(), amount*wallet.portion)).collect();
+    let distributed = shares.iter().fold(Uint128(0), |sum, (_, share)| sum + *share);
+    let remainder = (amount - distributed)?;
+    match dust_wallet{
+        Some(address) if remainder > Uint128(0) => {
+            shares.push((address.clone(), remainder));
+            Ok((shares, Uint128(0)))
+        },
+        _ => Ok((shares, remainder))
+    }
+}
+
+/// Picks the cheapest of the phase's tiers that `sender` belongs to and that
+/// still has room for `quantity` tokens. Also returns the most tokens any of
+/// the sender's tiers has room for, None when the sender is in none of them.
+fn best_tier<S: Storage>(storage: &S, phase: &Phase, sender: &HumanAddr, quantity: u32) -> StdResult<(Option<Tier>, Option<u32>)> {
+    let mut best: Option<Tier> = None;
+    let mut most_room: Option<u32> = None;
+    for tier in read_tiers(storage).load()?{
+        if !phase.tiers.contains(&tier.name) || !is_member(storage, &tier.name, sender){
+            continue;
         }
-        let user_info = read_user_info(&deps.storage,&sender.as_str());
-       
-        if user_info == None{
-            store_user_info(& mut deps.storage, &sender.as_str(), vec![token_id.clone()])?;
+        let wallet_room = tier.per_wallet_cap.u128().saturating_sub(read_tier_mints(storage, &tier.name, sender.as_str()) as u128);
+        let total_room = tier.allocation.u128().saturating_sub(read_tier_total(storage, &tier.name) as u128);
+        let room = wallet_room.min(total_room).min(u32::MAX as u128) as u32;
+        most_room = Some(most_room.map_or(room, |most| most.max(room)));
+        if room < quantity{
+            continue;
         }
-        else {
-            let mut new_user_info = user_info.unwrap();
-            
-            new_user_info.push(token_id.clone());
-            store_user_info(& mut deps.storage, &sender.as_str(), new_user_info)?;
+        if best.as_ref().map_or(true, |best| tier.price < best.price){
+            best = Some(tier);
         }
-       
-        let mut  res = vec![
-           snip721::mint_nft_msg(Some(token_id),
-            Some(sender), 
-            Some(Metadata{
-                token_uri:None,
-                extension:Some(Extension{
-                    image:Some(image_url),
-                    image_data:None,
-                    external_url:Some(token_url),
-                    description:None,
-                    name:None,
-                    attributes:None,
-                    background_color:None,
-                    animation_url:None,
-                    youtube_url:None,
-                    media:None,
-                    protected_attributes:None
-                })
-            }),
-            None, None, None,
-            RESPONSE_BLOCK_SIZE, 
-             state.nft_contract_hash, 
-            state.nft_address)?
-        ];
-        
-        
-         for reward_member in state.reward_wallet{
-            res.push(
-                snip20::transfer_msg(reward_member.address, 
-                    amount*reward_member.portion, 
-                    None,
-                    None,
-                    RESPONSE_BLOCK_SIZE, 
-                    state.token_contract_hash.clone(), 
-                    state.token_address.clone())?
-            )
+    }
+    Ok((best, most_room))
+}
+
+/// Resolves the sale phase at `time`. A scheduled phase wins; otherwise the
+/// manual SetSaleFlag switches open an unbounded private or public phase. The
+/// private phase admits every tier.
+fn active_phase<S: Storage>(storage: &S, state: &State, time: u64) -> StdResult<Option<Phase>> {
+    let phases = read_phases(storage).load()?;
+    if let Some(phase) = phases.into_iter().find(|phase| phase.start <= time && time < phase.end){
+        return Ok(Some(phase));
+    }
+    if state.private_mint{
+        return Ok(Some(Phase{
+            name:"private".to_string(),
+            start:0,
+            end:u64::MAX,
+            price:state.private_price,
+            per_wallet_limit:Some(state.maximum_count),
+            whitelist_only:true,
+            tiers:read_tiers(storage).load()?.into_iter().map(|tier| tier.name).collect(),
+            tiers_only:false
+        }));
+    }
+    if state.public_mint{
+        return Ok(Some(Phase{
+            name:"public".to_string(),
+            start:0,
+            end:u64::MAX,
+            price:state.public_price,
+            per_wallet_limit:state.public_maximum_count,
+            whitelist_only:false,
+            tiers:vec![],
+            tiers_only:false
+        }));
+    }
+    Ok(None)
+}
+
+/// Swap-and-pop draw from the pool of unminted indices: the drawn position is
+/// refilled with the last index of the pool, which then shrinks by one. Every
+/// remaining index is equally likely and each draw costs a constant amount.
+fn draw_index<S: Storage>(storage: &mut S, remaining: u32, rand: u32) -> StdResult<u32> {
+    let position = rand % remaining;
+    let last = remaining - 1;
+    let index = read_pool_index(storage, position).unwrap_or(position);
+    if position != last{
+        let last_index = read_pool_index(storage, last).unwrap_or(last);
+        store_pool_index(storage, position, last_index)?;
+    }
+    remove_pool_index(storage, last);
+    Ok(index)
+}
+
+/// Reads the Receive payload; an empty payload mints a single token.
+fn parse_mint_msg(msg: &Binary) -> StdResult<MintMsg> {
+    if msg.as_slice().is_empty(){
+        return Ok(MintMsg::default());
+    }
+    from_binary(msg)
+}
+
+fn token_metadata(image: Option<String>, external_url: Option<String>) -> Metadata {
+    Metadata{
+        token_uri:None,
+        extension:Some(Extension{
+            image,
+            image_data:None,
+            external_url,
+            description:None,
+            name:None,
+            attributes:None,
+            background_color:None,
+            animation_url:None,
+            youtube_url:None,
+            media:None,
+            protected_attributes:None
+        })
+    }
+}
+
+/// Token id and metadata of a global token index, with the uploaded on-chain
+/// fields when the admin set any.
+fn art_metadata<S: Storage>(storage: &S, collections: &[Collection], index: u128) -> StdResult<(String, Metadata)> {
+    let (token_id, token_url, image_url) = token_info(collections, index)?;
+    let (collection, index) = find_collection(collections, index)?;
+    let mut metadata = token_metadata(Some(image_url), Some(token_url));
+    if let (Some(uploaded), Some(extension)) = (read_token_metadata(storage, &collection.name, index as u32), metadata.extension.as_mut()){
+        extension.name = uploaded.name;
+        extension.description = uploaded.description;
+        extension.attributes = uploaded.attributes;
+        extension.animation_url = uploaded.animation_url;
+        if uploaded.image.is_some(){
+            extension.image = uploaded.image;
         }
-          Ok(HandleResponse {
-             messages:res,
-              log: vec![],
-             data: None,
-         })
     }
+    Ok((token_id, metadata))
+}
 
-    
+/// Private metadata of a global token index from its collection's template,
+/// None when the collection has none.
+fn unlockable_metadata(collections: &[Collection], index: u128, rng: &mut Prng) -> StdResult<Option<Metadata>> {
+    let (collection, index) = find_collection(collections, index)?;
+    let template = match &collection.private_metadata{
+        Some(template) => template,
+        None => return Ok(None)
+    };
+    let image_url = match &template.image_uri{
+        Some(uri) => Some(template::render(image_format(collection), &collection.name, uri, index)?),
+        None => None
+    };
+    let mut metadata = token_metadata(image_url, template.external_url.clone());
+    if let Some(extension) = metadata.extension.as_mut(){
+        extension.description = template.description.clone();
+        if template.secret_code{
+            extension.attributes = Some(vec![Trait{
+                display_type:None,
+                trait_type:Some("Secret Code".to_string()),
+                value:base64::encode(&rng.rand_bytes()[..12]),
+                max_value:None
+            }]);
+        }
+    }
+    Ok(Some(metadata))
 }
 
+/// Global index of the art shown by pre-reveal token `id`.
+fn revealed_index(state: &State, reveal: &RevealInfo, id: u32) -> StdResult<u128> {
+    match (id as u128 + reveal.offset as u128).checked_rem(state.total_supply.u128()){
+        Some(index) => Ok(index),
+        None => Err(ContractError::NothingToReveal {}.into())
+    }
+}
 
-pub fn set_maximum_nft<S: Storage, A: Api, Q: Querier>(
+/// Metadata of pre-reveal token `id` once the reveal offset is known.
+fn revealed_metadata<S: Storage>(storage: &S, collections: &[Collection], state: &State, reveal: &RevealInfo, id: u32) -> StdResult<Metadata> {
+    let (_, metadata) = art_metadata(storage, collections, revealed_index(state, reveal, id)?)?;
+    Ok(metadata)
+}
+
+/// Builds the token id, metadata url and image url of a global token index.
+fn token_info(collections: &[Collection], index: u128) -> StdResult<(String, String, String)> {
+    let (collection, index) = find_collection(collections, index)?;
+    let preview = render_uris(collection, index)?;
+    Ok((preview.token_id, preview.metadata_uri, preview.image_uri))
+}
+
+/// Renders a collection's formats for an index inside the collection.
+fn render_uris(collection: &Collection, index: u128) -> StdResult<UriPreview> {
+    Ok(UriPreview{
+        token_id:template::render(token_id_format(collection), &collection.name, "", index)?,
+        metadata_uri:template::render(metadata_format(collection), &collection.name, &collection.metadata_uri, index)?,
+        image_uri:template::render(image_format(collection), &collection.name, &collection.image_uri, index)?
+    })
+}
+
+fn token_id_format(collection: &Collection) -> &str {
+    collection.token_id_format.as_deref().unwrap_or(template::DEFAULT_TOKEN_ID)
+}
+
+fn metadata_format(collection: &Collection) -> &str {
+    collection.metadata_format.as_deref().unwrap_or(template::DEFAULT_METADATA)
+}
+
+fn image_format(collection: &Collection) -> &str {
+    collection.image_format.as_deref().unwrap_or(template::DEFAULT_IMAGE)
+}
+
+
+pub fn create_viewing_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: Env,
-    amount:Uint128
+    env: Env,
+    entropy:String
 ) -> StdResult<HandleResponse> {
-    let state = config_read(&deps.storage).load()?;
-    if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
-    }
-    config(&mut deps.storage).update(|mut state| {
-        state.maximum_count = amount;
-     
-        Ok(state)
-    })?;
+    let prng_seed = read_prng_seed(&deps.storage).load()?;
+    let pool_entropy = mix_entropy(&mut deps.storage, &env, entropy.as_bytes())?;
+    let key = ViewingKey::new(&env, &prng_seed, &pool_entropy);
+    store_viewing_key(&mut deps.storage, env.message.sender.as_str(), &key.to_hashed())?;
+
+    Ok(HandleResponse {
+        messages:vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
+    })
+}
+
+pub fn set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key:String
+) -> StdResult<HandleResponse> {
+    let key = ViewingKey(key);
+    store_viewing_key(&mut deps.storage, env.message.sender.as_str(), &key.to_hashed())?;
 
-   
     Ok(HandleResponse::default())
 }
 
-pub fn set_total_supply<S: Storage, A: Api, Q: Querier>(
+pub fn revoke<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: Env,
-    amount:Uint128
+    env: Env,
+    permit_name:String
 ) -> StdResult<HandleResponse> {
-    let state = config_read(&deps.storage).load()?;
-    if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
-    }
-    config(&mut deps.storage).update(|mut state| {
-        state.total_supply = amount;
-     
-        Ok(state)
-    })?;
+    revoke_permit(&mut deps.storage, env.message.sender.as_str(), &permit_name)?;
 
-   
     Ok(HandleResponse::default())
 }
 
-pub fn set_admin<S: Storage, A: Api, Q: Querier>(
+pub fn reveal_seed<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    address:HumanAddr
+    seed:Binary
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
     }
-    config(&mut deps.storage).update(|mut state| {
-        state.admin = address;
-     
-        Ok(state)
-    })?;
+    if read_mint_seed(&deps.storage).may_load()?.is_some(){
+        return Err(ContractError::SeedAlreadyRevealed {}.into())
+    }
+    if sha_256(seed.as_slice())[..] != *state.seed_commitment.as_slice(){
+        return Err(ContractError::SeedMismatch {}.into())
+    }
+    save_mint_seed(&mut deps.storage).save(&seed.as_slice().to_vec())?;
 
-   
     Ok(HandleResponse::default())
 }
 
-
-
-pub fn set_nft_address<S: Storage, A: Api, Q: Querier>(
+pub fn set_placeholder<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    address:HumanAddr,
-    nft_contract_hash:String
+    uri:Option<String>
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    // tokens are either all drawn from the pool or all numbered in order
+    if state.count != Uint128(0){
+        return Err(ContractError::MintingStarted {}.into())
     }
     config(&mut deps.storage).update(|mut state| {
-        state.nft_address = address;
-        state.nft_contract_hash = nft_contract_hash;
+        state.placeholder_uri = uri;
         Ok(state)
     })?;
 
-   
     Ok(HandleResponse::default())
 }
 
-pub fn set_token_address<S: Storage, A: Api, Q: Querier>(
+pub fn close_sale<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    address:HumanAddr,
-    token_contract_hash:String
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    if state.sale_closed{
+        return Err(ContractError::SaleClosed {}.into())
     }
     config(&mut deps.storage).update(|mut state| {
-        state.token_address = address;
-        state.token_contract_hash = token_contract_hash;
+        state.sale_closed = true;
         Ok(state)
     })?;
 
-   
     Ok(HandleResponse::default())
 }
 
+/// Nothing is left to mint, either sold out or closed by the admin.
+fn sale_over(state: &State) -> bool {
+    state.sale_closed || state.count == state.total_supply
+}
 
-pub fn set_reward_wallet<S: Storage, A: Api, Q: Querier>(
+pub fn reveal<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: Env,
-    wallet:Vec<Wallet>
+    env: Env,
+    seed:Binary
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
-    if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+    if env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
     }
-    let mut portion = Decimal::zero();
-    for personal_wallet in wallet.clone(){
-        portion = personal_wallet.portion + portion;
+    if state.placeholder_uri.is_none(){
+        return Err(ContractError::NotPreReveal {}.into())
+    }
+    if read_reveal(&deps.storage).may_load()?.is_some(){
+        return Err(ContractError::AlreadyRevealed {}.into())
+    }
+    // with the public offset, art of tokens minted after the reveal could be predicted
+    if !sale_over(&state){
+        return Err(ContractError::SaleNotOver { remaining: (state.total_supply - state.count)? }.into())
+    }
+    if state.count == Uint128(0){
+        return Err(ContractError::NothingToReveal {}.into())
     }
+    // the pool keeps the admin from choosing the offset through the seed
+    let pool_entropy = mix_entropy(&mut deps.storage, &env, b"reveal")?;
+    let random = new_entropy(&env, seed.as_slice(), &pool_entropy);
+    let offset = (u32::from_be_bytes([random[0], random[1], random[2], random[3]]) as u128 % state.total_supply.u128()) as u32;
+    let reveal = RevealInfo{
+        offset,
+        updated:0,
+        target:state.count.u128() as u32
+    };
+    reveal_batch(deps, &env, &state, reveal)
+}
 
-    if portion != Decimal::one(){
-        return Err(StdError::generic_err("The sum must be equal to 1"))
+pub fn continue_reveal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    let reveal = match read_reveal(&deps.storage).may_load()?{
+        Some(reveal) if reveal.updated < reveal.target => reveal,
+        _ => return Err(ContractError::NothingToReveal {}.into())
     };
+    reveal_batch(deps, &env, &state, reveal)
+}
 
-    config(&mut deps.storage).update(|mut state| {
-        state.reward_wallet = wallet;
-     
-        Ok(state)
-    })?;
+/// Sends set_metadata for the next REVEAL_BATCH_SIZE tokens minted before the reveal.
+fn reveal_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    state: &State,
+    mut reveal: RevealInfo
+) -> StdResult<HandleResponse> {
+    let collections = read_collections(&deps.storage).load()?;
+    let mut code_rng = Prng::new(&mix_entropy(&mut deps.storage, env, b"secret_code")?, &[]);
+    let end = reveal.updated.saturating_add(REVEAL_BATCH_SIZE).min(reveal.target);
+    let mut messages = vec![];
+    for id in reveal.updated..end{
+        // pre-reveal tokens took the id of their slot in mint order
+        let (token_id, _, _) = token_info(&collections, id as u128)?;
+        messages.push(
+            snip721::set_metadata_msg(token_id,
+                Some(revealed_metadata(&deps.storage, &collections, state, &reveal, id)?),
+                unlockable_metadata(&collections, revealed_index(state, &reveal, id)?, &mut code_rng)?,
+                None,
+                RESPONSE_BLOCK_SIZE,
+                state.nft_contract_hash.clone(),
+                state.nft_address.clone())?
+        );
+    }
+    reveal.updated = end;
+    save_reveal(&mut deps.storage).save(&reveal)?;
 
-   
-    Ok(HandleResponse::default())
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("revealed", end), log("remaining", reveal.target - end)],
+        data: None,
+    })
 }
 
-pub fn set_price<S: Storage, A: Api, Q: Querier>(
+pub fn claim_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let pending = read_pending_rewards(&deps.storage, env.message.sender.as_str());
+    if pending == Uint128(0){
+        return Err(ContractError::NoRewards {}.into())
+    }
+    store_pending_rewards(&mut deps.storage, env.message.sender.as_str(), Uint128(0))?;
+
+    Ok(HandleResponse {
+        messages:vec![
+            snip20::transfer_msg(env.message.sender,
+                pending,
+                None,
+                None,
+                RESPONSE_BLOCK_SIZE,
+                state.token_contract_hash,
+                state.token_address)?
+        ],
+        log: vec![],
+        data: None,
+    })
+}
+
+pub fn set_dust_wallet<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    public_price:Uint128,
-    private_price:Uint128
+    address:Option<HumanAddr>
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
     }
     config(&mut deps.storage).update(|mut state| {
-        state.public_price = public_price;
-        state.private_price = private_price;
-     
+        state.dust_wallet = address;
         Ok(state)
     })?;
 
-   
     Ok(HandleResponse::default())
 }
 
-
-
-pub fn set_mint_time<S: Storage, A: Api, Q: Querier>(
+pub fn sweep_dust<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    private_mint:bool,
-    public_mint:bool
+    recipient:Option<HumanAddr>
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
-
-    if private_mint==true && public_mint == true{
-        return Err(StdError::generic_err(
-            "You can not set both true"
-        ))
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    let dust = read_dust(&deps.storage);
+    if dust == Uint128(0){
+        return Err(ContractError::NoDust {}.into())
     }
+    store_dust(&mut deps.storage, Uint128(0))?;
+
+    Ok(HandleResponse {
+        messages:vec![
+            snip20::transfer_msg(recipient.unwrap_or(state.admin),
+                dust,
+                None,
+                None,
+                RESPONSE_BLOCK_SIZE,
+                state.token_contract_hash,
+                state.token_address)?
+        ],
+        log: vec![],
+        data: None,
+    })
+}
 
+pub fn set_reward_mode<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    push:bool
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
     }
+    // balances credited in pull mode stay claimable after switching to push
     config(&mut deps.storage).update(|mut state| {
-        state.private_mint = private_mint;
-        state.public_mint = public_mint;
-     
+        state.push_rewards = push;
         Ok(state)
     })?;
 
-   
     Ok(HandleResponse::default())
 }
 
-pub fn set_white_members<S: Storage, A: Api, Q: Querier>(
+pub fn set_wallet_limits<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    new_members:Vec<HumanAddr>
+    public_maximum_count:Option<Uint128>,
+    wallet_maximum_count:Option<Uint128>
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
     }
-    store_members(&mut deps.storage).save(&new_members)?;
+    config(&mut deps.storage).update(|mut state| {
+        state.public_maximum_count = public_maximum_count;
+        state.wallet_maximum_count = wallet_maximum_count;
+        Ok(state)
+    })?;
 
     Ok(HandleResponse::default())
 }
 
-
-pub fn add_white_user<S: Storage, A: Api, Q: Querier>(
+pub fn set_maximum_nft<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    member:HumanAddr
+    amount:Uint128
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
-    }
-    let crr_members = read_members(&deps.storage).load()?;
-      for crr_member in crr_members{
-        if crr_member == member{
-         return Err(StdError::generic_err("repeated user"));         
-        }
+        return Err(ContractError::Unauthorized {}.into())
     }
-    store_members(&mut deps.storage).update(|mut members| {
-        members.push(member);
+    config(&mut deps.storage).update(|mut state| {
+        state.maximum_count = amount;
      
-        Ok(members)
+        Ok(state)
     })?;
 
+   
     Ok(HandleResponse::default())
 }
 
-
-pub fn add_metadata<S: Storage, A: Api, Q: Querier>(
+pub fn set_admin<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    key:String,
-    new_metadata:Vec<String>
+    address:HumanAddr
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
     }
+    config(&mut deps.storage).update(|mut state| {
+        state.admin = address;
+     
+        Ok(state)
+    })?;
+
    
-    store_url_info(&mut deps.storage, &key, new_metadata)?;
     Ok(HandleResponse::default())
 }
 
 
-pub fn set_metadata<S: Storage, A: Api, Q: Querier>(
+
+pub fn set_nft_address<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    address:HumanAddr,
+    nft_contract_hash:String
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    config(&mut deps.storage).update(|mut state| {
+        state.nft_address = address;
+        state.nft_contract_hash = nft_contract_hash;
+        Ok(state)
+    })?;
+
+   
+    Ok(HandleResponse::default())
+}
+
+pub fn set_token_address<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     _env: Env,
-    new_metadata:Vec<String>
+    address:HumanAddr,
+    token_contract_hash:String
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
     if _env.message.sender != state.admin{
-        return Err(StdError::generic_err(
-            "Unauthorized"
-        ))
+        return Err(ContractError::Unauthorized {}.into())
     }
+    config(&mut deps.storage).update(|mut state| {
+        state.token_address = address;
+        state.token_contract_hash = token_contract_hash;
+        Ok(state)
+    })?;
+
    
-    save_metadata(&mut deps.storage).save(&new_metadata)?;
+    Ok(HandleResponse::default())
+}
+
+
+pub fn set_reward_wallet<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    wallet:Vec<Wallet>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    let mut portion = Decimal::zero();
+    for personal_wallet in wallet.clone(){
+        portion = personal_wallet.portion + portion;
+    }
+
+    if portion != Decimal::one(){
+        return Err(ContractError::InvalidRewardSplit {}.into())
+    };
+
+    config(&mut deps.storage).update(|mut state| {
+        state.reward_wallet = wallet;
+     
+        Ok(state)
+    })?;
+
    
     Ok(HandleResponse::default())
 }
 
-pub fn set_random<S: Storage, A: Api, Q: Querier>(
+pub fn set_royalties<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
+    _env: Env,
+    royalties:Vec<Wallet>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    validate_royalties(&royalties)?;
+
+    config(&mut deps.storage).update(|mut state| {
+        state.royalties = royalties;
+        Ok(state)
+    })?;
+
+    Ok(HandleResponse::default())
+}
+
+pub fn set_price<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    public_price:Uint128,
+    private_price:Uint128
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    config(&mut deps.storage).update(|mut state| {
+        state.public_price = public_price;
+        state.private_price = private_price;
+     
+        Ok(state)
+    })?;
+
+   
+    Ok(HandleResponse::default())
+}
+
+
+
+pub fn set_mint_time<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    private_mint:bool,
+    public_mint:bool
 ) -> StdResult<HandleResponse> {
     let state = config_read(&deps.storage).load()?;
+
+    if private_mint==true && public_mint == true{
+        return Err(ContractError::ConflictingSaleFlags {}.into())
+    }
+
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    config(&mut deps.storage).update(|mut state| {
+        state.private_mint = private_mint;
+        state.public_mint = public_mint;
+     
+        Ok(state)
+    })?;
+
    
-    let prng_seed: Vec<u8> = sha_256(base64::encode("entropy").as_bytes()).to_vec();
-    let random_seed = new_entropy(&env, prng_seed.as_ref(), prng_seed.as_ref());
+    Ok(HandleResponse::default())
+}
+
+pub fn add_white_users<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    members:Vec<HumanAddr>,
+    tier:Option<String>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    let list = member_list(&deps.storage, tier)?;
+    // members that are already on the list are skipped
+    for member in members.iter(){
+        add_member(&mut deps.storage, &list, member)?;
+    }
+
+    Ok(HandleResponse::default())
+}
+
+
+pub fn remove_white_users<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    members:Vec<HumanAddr>,
+    tier:Option<String>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    let list = member_list(&deps.storage, tier)?;
+    for member in members.iter(){
+        remove_member(&mut deps.storage, &list, member)?;
+    }
+
+    Ok(HandleResponse::default())
+}
+
+/// Names the member list a whitelist message targets: the tier's own list, or
+/// the plain whitelist (empty name) when no tier is given.
+fn member_list<S: Storage>(storage: &S, tier: Option<String>) -> StdResult<String> {
+    match tier{
+        None => Ok(String::new()),
+        Some(name) => {
+            if !read_tiers(storage).load()?.iter().any(|tier| tier.name == name){
+                return Err(ContractError::TierNotFound { name }.into());
+            }
+            Ok(name)
+        }
+    }
+}
+
+pub fn set_tiers<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    tiers:Vec<Tier>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    for (i, tier) in tiers.iter().enumerate(){
+        if tier.name.is_empty(){
+            return Err(ContractError::EmptyName { kind: "tier".to_string() }.into());
+        }
+        if tiers[..i].iter().any(|other| other.name == tier.name){
+            return Err(ContractError::DuplicateName { kind: "tier".to_string(), name: tier.name.clone() }.into());
+        }
+        // tier lists share the member namespace with the raffle lists
+        if tier.name.starts_with(RAFFLE_PREFIX){
+            return Err(ContractError::ReservedName { kind: "tier".to_string(), name: tier.name.clone() }.into());
+        }
+        // a removed tier's members and counters are still in storage
+        if is_retired_tier(&deps.storage, &tier.name){
+            return Err(ContractError::RetiredTier { name: tier.name.clone() }.into());
+        }
+    }
+    for old in read_tiers(&deps.storage).load()?{
+        if !tiers.iter().any(|tier| tier.name == old.name){
+            retire_tier(&mut deps.storage, &old.name)?;
+        }
+    }
+    store_tiers(&mut deps.storage).save(&tiers)?;
+
+    Ok(HandleResponse::default())
+}
+
+
+pub fn set_merkle_root<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    root:Option<Binary>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    if let Some(root) = &root{
+        if root.as_slice().len() != 32{
+            return Err(ContractError::InvalidMerkleRoot {}.into());
+        }
+    }
+    config(&mut deps.storage).update(|mut state| {
+        state.merkle_root = root;
+        Ok(state)
+    })?;
+
+    Ok(HandleResponse::default())
+}
+
+
+pub fn upload_token_metadata<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    collection:String,
+    start:u32,
+    metadata:Vec<TokenMetadata>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    // minted tokens already carry their metadata
+    if state.count != Uint128(0){
+        return Err(ContractError::MintingStarted {}.into())
+    }
+    let collections = read_collections(&deps.storage).load()?;
+    let supply = match collections.iter().find(|c| c.name == collection){
+        Some(c) => c.supply.u128(),
+        None => return Err(ContractError::CollectionNotFound { name: collection }.into())
+    };
+    let uploaded = read_upload_progress(&deps.storage, &collection);
+    if start > uploaded{
+        return Err(ContractError::MetadataGap { collection, uploaded }.into())
+    }
+    let end = start as u128 + metadata.len() as u128;
+    if end > supply{
+        return Err(ContractError::IndexOutOfRange { index: end - 1 }.into())
+    }
+
+    for (i, token) in metadata.iter().enumerate(){
+        store_token_metadata(&mut deps.storage, &collection, start + i as u32, token)?;
+    }
+    let uploaded = uploaded.max(end as u32);
+    store_upload_progress(&mut deps.storage, &collection, uploaded)?;
+
+    Ok(HandleResponse {
+        messages:vec![],
+        log: vec![log("uploaded", uploaded), log("supply", supply)],
+        data: None,
+    })
+}
+
+pub fn add_raffle_entrants<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    raffle:String,
+    entrants:Vec<HumanAddr>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    if read_raffle(&deps.storage, &raffle).is_some(){
+        return Err(ContractError::RaffleAlreadyDrawn { name: raffle }.into())
+    }
+    let list = raffle_list(&raffle);
+    for entrant in entrants.iter(){
+        add_member(&mut deps.storage, &list, entrant)?;
+    }
+    Ok(HandleResponse::default())
+}
+
+pub fn draw_raffle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    raffle:String,
+    winners:u32
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    if read_raffle(&deps.storage, &raffle).is_some(){
+        return Err(ContractError::RaffleAlreadyDrawn { name: raffle }.into())
+    }
+    if winners == 0{
+        return Err(ContractError::InvalidQuantity {}.into())
+    }
+    let list = raffle_list(&raffle);
+    let entrants = read_member_count(&deps.storage, &list);
+    if winners > entrants{
+        return Err(ContractError::NotEnoughEntrants { entrants }.into())
+    }
+
+    // partial Fisher-Yates over the entrant positions; like draw_index only
+    // the swapped positions are kept, so memory grows with the winners
+    let random_seed = mix_entropy(&mut deps.storage, &env, raffle.as_bytes())?;
     let mut rng = ChaChaRng::from_seed(random_seed);
-    
-    let count = Uint128::u128(&state.total_supply);
-        
-    let num = (rng.next_u32() % (count as u32)) as u16 ;
-    save_rand(&mut deps.storage).save(&num)?;
+    let mut swapped: BTreeMap<u32, u32> = BTreeMap::new();
+    let mut drawn = vec![];
+    for i in 0..winners{
+        let j = i + rng.next_u32() % (entrants - i);
+        let position = *swapped.get(&j).unwrap_or(&j);
+        if j != i{
+            swapped.insert(j, *swapped.get(&i).unwrap_or(&i));
+        }
+        if let Some(winner) = read_member_at(&deps.storage, &list, position){
+            drawn.push(winner);
+        }
+    }
+    store_raffle(&mut deps.storage, &raffle, &RaffleResult{
+        winners:drawn,
+        entrants,
+        block_height:env.block.height
+    })?;
 
     Ok(HandleResponse::default())
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetStateInfo {} => to_binary(&query_state_info(deps)?),
-        QueryMsg::GetWhiteUsers {} => to_binary(&query_white_users(deps)?),
-        QueryMsg::GetUserInfo { address } => to_binary(&query_user_info(deps,address)?),
-        QueryMsg::GetMetadata { key} => to_binary(&query_metadata(deps,key)?),
-        QueryMsg::GetRand {  }=> to_binary(&query_random(deps)?)
+pub fn set_collections<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    collections:Vec<Collection>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    // replacing the registry shifts the global index of every token
+    if state.count != Uint128(0){
+        return Err(ContractError::MintingStarted {}.into())
+    }
+    validate_collections(&collections)?;
+    save_collections(&mut deps.storage, &collections)?;
+
+    Ok(HandleResponse::default())
+}
+
+pub fn add_collection<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    collection:Collection
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    // tokens of a collection added after the pool was laid out could never be drawn
+    if state.count != Uint128(0){
+        return Err(ContractError::MintingStarted {}.into())
     }
+    let mut collections = read_collections(&deps.storage).load()?;
+    collections.push(collection);
+    validate_collections(&collections)?;
+    save_collections(&mut deps.storage, &collections)?;
+
+    Ok(HandleResponse::default())
 }
 
-fn query_state_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<State> {
-    let state = config_read(&deps.storage).load()?;
-    Ok(state)
-}
+pub fn remove_collection<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    name:String
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    if state.count != Uint128(0){
+        return Err(ContractError::MintingStarted {}.into())
+    }
+    let mut collections = read_collections(&deps.storage).load()?;
+    let len = collections.len();
+    collections.retain(|collection| collection.name != name);
+    if collections.len() == len{
+        return Err(ContractError::CollectionNotFound { name }.into());
+    }
+    save_collections(&mut deps.storage, &collections)?;
+
+    Ok(HandleResponse::default())
+}
+
+pub fn set_phases<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    mut phases:Vec<Phase>
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    if _env.message.sender != state.admin{
+        return Err(ContractError::Unauthorized {}.into())
+    }
+    phases.sort_by_key(|phase| phase.start);
+    for (i, phase) in phases.iter().enumerate(){
+        if phase.name.is_empty(){
+            return Err(ContractError::EmptyName { kind: "phase".to_string() }.into());
+        }
+        if phase.start >= phase.end{
+            return Err(ContractError::InvalidPhaseWindow { name: phase.name.clone() }.into());
+        }
+        if i > 0 && phases[i-1].end > phase.start{
+            return Err(ContractError::OverlappingPhases { first: phases[i-1].name.clone(), second: phase.name.clone() }.into());
+        }
+        if phases[..i].iter().any(|other| other.name == phase.name){
+            return Err(ContractError::DuplicateName { kind: "phase".to_string(), name: phase.name.clone() }.into());
+        }
+    }
+    store_phases(&mut deps.storage).save(&phases)?;
+
+    Ok(HandleResponse::default())
+}
+
+fn validate_collections(collections: &[Collection]) -> StdResult<()> {
+    for (i, collection) in collections.iter().enumerate(){
+        if collection.name.is_empty(){
+            return Err(ContractError::EmptyName { kind: "collection".to_string() }.into());
+        }
+        if collection.supply == Uint128(0){
+            return Err(ContractError::ZeroSupply { name: collection.name.clone() }.into());
+        }
+        if collections[..i].iter().any(|other| other.name == collection.name){
+            return Err(ContractError::DuplicateName { kind: "collection".to_string(), name: collection.name.clone() }.into());
+        }
+        template::validate_token_id(token_id_format(collection))?;
+        template::validate(metadata_format(collection))?;
+        template::validate(image_format(collection))?;
+    }
+    Ok(())
+}
+
+fn collections_supply(collections: &[Collection]) -> Uint128 {
+    Uint128(collections.iter().map(|collection| collection.supply.u128()).sum())
+}
+
+/// Stores the registry and resizes the total supply to it. Uploaded metadata
+/// of collections that are removed or change supply is dropped, so a
+/// collection added later under the same name starts empty.
+fn save_collections<S: Storage>(storage: &mut S, collections: &[Collection]) -> StdResult<()> {
+    // metadata is keyed by name and index, the uris can change freely
+    for old in read_collections(storage).load()?{
+        if !collections.iter().any(|new| new.name == old.name && new.supply == old.supply){
+            clear_token_metadata(storage, &old.name);
+        }
+    }
+    store_collections(storage).save(&collections.to_vec())?;
+    config(storage).update(|mut state| {
+        state.total_supply = collections_supply(collections);
+        Ok(state)
+    })?;
+    Ok(())
+}
+
+/// Splits a global token index into its collection and the index inside it.
+fn find_collection(collections: &[Collection], index: u128) -> StdResult<(&Collection, u128)> {
+    let mut offset:u128 = 0;
+    for collection in collections{
+        let supply = collection.supply.u128();
+        if index < offset + supply{
+            return Ok((collection, index - offset));
+        }
+        offset += supply;
+    }
+    Err(ContractError::IndexOutOfRange { index }.into())
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetSaleInfo { time } => to_binary(&query_sale_info(deps,time)?),
+        QueryMsg::GetConfig { key } => {
+            check_admin_key(deps, key)?;
+            to_binary(&query_state_info(deps)?)
+        },
+        QueryMsg::GetWhiteUsers { start_after, limit, tier } => to_binary(&query_white_users(deps,start_after,limit,tier)?),
+        QueryMsg::IsWhitelisted { address, tier } => to_binary(&is_member(&deps.storage, &member_list(&deps.storage, tier)?, &address)),
+        QueryMsg::GetUserInfo { address, key } => {
+            check_viewing_key(deps, &address, key)?;
+            to_binary(&query_user_info(deps,address)?)
+        },
+        QueryMsg::GetTokenMetadata { collection, index } => to_binary(&read_token_metadata(&deps.storage, &collection, index)),
+        QueryMsg::GetUploadProgress {  } => to_binary(&query_upload_progress(deps)?),
+        QueryMsg::PreviewUris { collection, index } => to_binary(&query_preview_uris(deps,collection,index)?),
+        QueryMsg::GetRaffle { raffle } => to_binary(&read_raffle(&deps.storage, &raffle)),
+        QueryMsg::GetCollections {  }=> to_binary(&query_collections(deps)?),
+        QueryMsg::GetPrivateCollections { key } => {
+            check_admin_key(deps, key)?;
+            to_binary(&query_private_collections(deps)?)
+        },
+        QueryMsg::GetMintedStatus { start, limit, key } => {
+            check_admin_key(deps, key)?;
+            to_binary(&query_minted_status(deps,start,limit)?)
+        },
+        QueryMsg::GetActivePhase { time } => to_binary(&query_active_phase(deps,time)?),
+        QueryMsg::GetTiers {  } => to_binary(&query_tiers(deps)?),
+        QueryMsg::GetPendingRewards { address, key } => {
+            check_viewing_key(deps, &address, key)?;
+            to_binary(&read_pending_rewards(&deps.storage, address.as_str()))
+        },
+        QueryMsg::GetDust {  } => to_binary(&read_dust(&deps.storage)),
+        QueryMsg::GetRoyalties {  } => to_binary(&config_read(&deps.storage).load()?.royalties),
+        QueryMsg::GetRevealInfo {  } => to_binary(&read_reveal(&deps.storage).may_load()?),
+        QueryMsg::GetProvenance {  } => to_binary(&query_provenance(deps)?),
+        QueryMsg::WithPermit { permit, query } => permit_query(deps, permit, query)
+    }
+}
+
+/// Fails unless `key` is the viewing key of `address`. A wallet without a key
+/// is checked against a dummy hash so both cases take the same time.
+fn check_viewing_key<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,address:&HumanAddr,key:String) -> StdResult<()> {
+    let hashed = read_viewing_key(&deps.storage, address.as_str());
+    let valid = match hashed{
+        Some(hashed) => ViewingKey(key).check_viewing_key(&hashed),
+        None => {
+            ViewingKey(key).check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+            false
+        }
+    };
+    if !valid{
+        return Err(ContractError::InvalidViewingKey {}.into())
+    }
+    Ok(())
+}
+
+fn check_admin_key<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,key:String) -> StdResult<()> {
+    let state = config_read(&deps.storage).load()?;
+    check_viewing_key(deps, &state.admin, key)
+}
+
+fn permit_query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,permit:Permit,query:QueryWithPermit) -> StdResult<Binary> {
+    let state = config_read(&deps.storage).load()?;
+    let address = permit::validate(&deps.api, &permit, &state.contract_address)?;
+    if is_permit_revoked(&deps.storage, address.as_str(), &permit.params.permit_name){
+        return Err(ContractError::PermitRevoked { permit_name: permit.params.permit_name }.into())
+    }
+    let permission = match query{
+        QueryWithPermit::UserInfo {  } => Permission::History,
+        QueryWithPermit::PendingRewards {  } => Permission::Balance,
+        QueryWithPermit::Config {  } | QueryWithPermit::MintedStatus { .. } | QueryWithPermit::PrivateCollections {  } => {
+            if address != state.admin{
+                return Err(ContractError::Unauthorized {}.into())
+            }
+            Permission::Owner
+        }
+    };
+    if !permit.check_permission(permission){
+        return Err(ContractError::MissingPermission {}.into())
+    }
+
+    match query{
+        QueryWithPermit::UserInfo {  } => to_binary(&query_user_info(deps,address)?),
+        QueryWithPermit::PendingRewards {  } => to_binary(&read_pending_rewards(&deps.storage, address.as_str())),
+        QueryWithPermit::Config {  } => to_binary(&state),
+        QueryWithPermit::MintedStatus { start, limit } => to_binary(&query_minted_status(deps,start,limit)?),
+        QueryWithPermit::PrivateCollections {  } => to_binary(&query_private_collections(deps)?)
+    }
+}
+
+fn query_sale_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,time:u64) -> StdResult<SaleInfoResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let phase = active_phase(&deps.storage, &state, time)?;
+    Ok(SaleInfoResponse {
+        public_price: state.public_price,
+        private_price: state.private_price,
+        public_mint: state.public_mint,
+        private_mint: state.private_mint,
+        phase,
+        minted: state.count,
+        total_supply: state.total_supply,
+        closed: state.sale_closed
+    })
+}
+
+fn query_state_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<State> {
+    let state = config_read(&deps.storage).load()?;
+    Ok(state)
+}
+
+fn query_preview_uris<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,collection:String,index:u32) -> StdResult<UriPreview> {
+    let collections = read_collections(&deps.storage).load()?;
+    let collection = match collections.iter().find(|c| c.name == collection){
+        Some(collection) => collection,
+        None => return Err(ContractError::CollectionNotFound { name: collection }.into())
+    };
+    if index as u128 >= collection.supply.u128(){
+        return Err(ContractError::IndexOutOfRange { index: index as u128 }.into())
+    }
+    render_uris(collection, index as u128)
+}
+
+fn query_upload_progress<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Vec<UploadProgress>> {
+    let collections = read_collections(&deps.storage).load()?;
+    Ok(collections.into_iter().map(|collection| UploadProgress{
+        uploaded:read_upload_progress(&deps.storage, &collection.name),
+        collection:collection.name,
+        supply:collection.supply
+    }).collect())
+}
+
+fn query_white_users<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,start_after:Option<HumanAddr>,limit:Option<u32>,tier:Option<String>) -> StdResult<Vec<HumanAddr>> {
+    let list = member_list(&deps.storage, tier)?;
+    let start = match start_after{
+        Some(member) => match read_member_position(&deps.storage, &list, &member){
+            Some(position) => position + 1,
+            None => return Err(ContractError::UnknownMember { address: member }.into())
+        },
+        None => 0
+    };
+    let limit = limit.unwrap_or(DEFAULT_WHITE_USERS_LIMIT).min(MAX_WHITE_USERS_LIMIT);
+    let end = start.saturating_add(limit).min(read_member_count(&deps.storage, &list));
+    let members = (start..end).filter_map(|position| read_member_at(&deps.storage, &list, position)).collect();
+    Ok(members)
+}
+
+fn query_collections<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Vec<CollectionInfo>> {
+    let collections = read_collections(&deps.storage).load()?;
+    Ok(collections.into_iter().map(|collection| CollectionInfo{
+        name:collection.name,
+        metadata_uri:collection.metadata_uri,
+        image_uri:collection.image_uri,
+        supply:collection.supply,
+        token_id_format:collection.token_id_format,
+        metadata_format:collection.metadata_format,
+        image_format:collection.image_format
+    }).collect())
+}
+
+fn query_private_collections<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Vec<Collection>> {
+    let collections = read_collections(&deps.storage).load()?;
+    Ok(collections)
+}
+
+fn query_minted_status<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,start:u32,limit:u32) -> StdResult<MintedStatusResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let total_supply = state.total_supply.u128() as u32;
+    let end = start.saturating_add(limit.min(MAX_MINTED_STATUS_LIMIT)).min(total_supply);
+    let minted = (start..end).map(|index| is_minted(&deps.storage, index)).collect();
+    Ok(MintedStatusResponse { start, minted })
+}
+
+fn query_active_phase<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,time:u64) -> StdResult<Option<Phase>> {
+    let state = config_read(&deps.storage).load()?;
+    active_phase(&deps.storage, &state, time)
+}
+
+fn query_tiers<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Vec<TierInfo>> {
+    let tiers = read_tiers(&deps.storage).load()?;
+    let tiers = tiers.into_iter().map(|tier| {
+        let minted = Uint128(read_tier_total(&deps.storage, &tier.name) as u128);
+        TierInfo { tier, minted }
+    }).collect();
+    Ok(tiers)
+}
+
+fn query_provenance<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<ProvenanceResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let reveal = read_reveal(&deps.storage).may_load()?;
+    // the seed would let anyone predict the remaining draws while tokens can
+    // still be minted; a reveal also needs the sale to be over
+    let mint_seed = if sale_over(&state){
+        read_mint_seed(&deps.storage).may_load()?.map(Binary)
+    } else {
+        None
+    };
+    Ok(ProvenanceResponse{
+        provenance_hash:state.provenance_hash,
+        starting_index:reveal.map(|reveal| reveal.offset),
+        total_supply:state.total_supply,
+        seed_commitment:state.seed_commitment,
+        mint_seed
+    })
+}
+
+fn query_user_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,address:HumanAddr) -> StdResult<UserInfoResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let token_ids = read_user_info(&deps.storage,&address.as_str()).unwrap_or_default();
+    let minted = Uint128(token_ids.len() as u128);
+    let remaining = state.wallet_maximum_count.map(|limit| (limit - minted).unwrap_or_default());
+    Ok(UserInfoResponse { token_ids, minted, remaining })
+}
+
+pub fn new_entropy(env: &Env, seed: &[u8], entropy: &[u8]) -> [u8; 32] {
+    let mut rng = Prng::new(seed, &env_entropy(env, entropy));
+
+    rng.rand_bytes()
+}
+
+/// Mixes the block, the sender and `entropy` into the stored pool, advances
+/// the pool and returns bytes for this call that differ from the new pool.
+pub fn mix_entropy<S: Storage>(storage: &mut S, env: &Env, entropy: &[u8]) -> StdResult<[u8; 32]> {
+    let pool = read_entropy_pool(storage).load()?;
+    let mut rng = Prng::new(&pool, &env_entropy(env, entropy));
+    save_entropy_pool(storage).save(&rng.rand_bytes().to_vec())?;
+
+    Ok(rng.rand_bytes())
+}
+
+fn env_entropy(env: &Env, entropy: &[u8]) -> Vec<u8> {
+    // 16 here represents the lengths in bytes of the block height and time.
+    let entropy_len = 16 + env.message.sender.len() + entropy.len();
+    let mut rng_entropy = Vec::with_capacity(entropy_len);
+    rng_entropy.extend_from_slice(&env.block.height.to_be_bytes());
+    rng_entropy.extend_from_slice(&env.block.time.to_be_bytes());
+    rng_entropy.extend_from_slice(&env.message.sender.0.as_bytes());
+    rng_entropy.extend_from_slice(entropy);
+    rng_entropy
+}
+
+
+#[cfg(test)]
+mod tests {
+
+
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, CosmosMsg, WasmMsg};
+    use crate::state::read_minted_page;
+    use crate::msg::PrivateMetadata;
+
+    fn reveal_seed<S: Storage, A: Api, Q: Querier>(deps: &mut Extern<S, A, Q>) {
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::RevealSeed { seed: Binary(b"secret".to_vec()) };
+        let _res = handle(deps, env, msg).unwrap();
+    }
+
+    /// A snip20 Receive of `amount` from `sender`, forwarded by the token contract.
+    fn receive_msg(sender: &str, amount: u128, mint: MintMsg) -> (Env, HandleMsg) {
+        let env = mock_env("token_address", &vec![]);
+        (env, HandleMsg::Receive {
+            sender: HumanAddr::from(sender),
+            from: HumanAddr::from(sender),
+            amount: Uint128(amount),
+            msg: to_binary(&mint).unwrap()
+        })
+    }
+
+    fn mint_msg(sender: &str, quantity: u32, amount: u128) -> (Env, HandleMsg) {
+        receive_msg(sender, amount, MintMsg{ quantity: Some(quantity), ..MintMsg::default() })
+    }
+
+    fn at_time(mut env: Env, time: u64) -> Env {
+        env.block.time = time;
+        env
+    }
+
+    /// InitMsg most tests start from; tests override the fields they exercise.
+    fn init_msg() -> InitMsg {
+        InitMsg {
+            white_members: vec![],
+            admin : HumanAddr::from("admin"),
+            maximum_count :Uint128(2),
+            public_price : Uint128(1000),
+            private_price:Uint128(500) ,
+            reward_wallet : vec![Wallet{
+                address:HumanAddr::from("reward1"),
+                portion:Decimal::one()
+            }],
+            token_address:HumanAddr::from("token_address"),
+            token_contract_hash :"token_hash".to_string(),
+            push_rewards : None,
+            collections : test_collections(),
+            prng_seed : Binary(b"seed".to_vec()),
+            seed_commitment : Binary(sha_256(b"secret").to_vec()),
+            provenance_hash : Binary(sha_256(b"images").to_vec())
+        }
+    }
+
+    fn test_collections() -> Vec<Collection> {
+        vec![Collection{
+            name:"SteamPunk_Human".to_string(),
+            metadata_uri:"human".to_string(),
+            image_uri:"human".to_string(),
+            supply:Uint128(2),
+            private_metadata:None,
+            token_id_format:None,
+            metadata_format:None,
+            image_format:None
+        },
+        Collection{
+            name:"SteamPunk_Bull".to_string(),
+            metadata_uri:"bull".to_string(),
+            image_uri:"bull".to_string(),
+            supply:Uint128(2),
+            private_metadata:None,
+            token_id_format:None,
+            metadata_format:None,
+            image_format:None
+        },
+        Collection{
+            name:"SteamPunk_Robot".to_string(),
+            metadata_uri:"robot".to_string(),
+            image_uri:"robot".to_string(),
+            supply:Uint128(1),
+            private_metadata:None,
+            token_id_format:None,
+            metadata_format:None,
+            image_format:None
+        }]
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &coins(1000, "earth"));
+
+        let msg = InitMsg {
+            white_members : vec![HumanAddr::from("white1"),HumanAddr::from("white2")],
+            maximum_count : Uint128(1),
+            public_price : Uint128(500000),
+            private_price : Uint128(600000),
+            reward_wallet : vec![Wallet{
+                address:HumanAddr::from("reward1"),
+                portion:Decimal::from_ratio(7 as u128,100 as u128)
+            },
+            Wallet{
+                address:HumanAddr::from("reward2"),
+                portion:Decimal::from_ratio(3 as u128,100 as u128)
+            }
+            ],
+            ..init_msg()
+        };
+        
+        // we can just call .unwrap() to assert this was a success
+        let res = init(&mut deps, env.clone(), msg).unwrap();
+        assert_eq!(1, res.messages.len());
+      
+       
+    }
+
+    #[test]
+    fn test_state() {
+         let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            white_members : vec![HumanAddr::from("white1"),HumanAddr::from("white2")],
+            maximum_count : Uint128(1),
+            public_price : Uint128(500000),
+            private_price : Uint128(600000),
+            reward_wallet : vec![Wallet{
+                address:HumanAddr::from("reward1"),
+                portion:Decimal::from_ratio(7 as u128,100 as u128)
+            },
+            Wallet{
+                address:HumanAddr::from("reward2"),
+                portion:Decimal::from_ratio(3 as u128,100 as u128)
+            }
+            ],
+            ..init_msg()
+        };
+        
+        let res = init(&mut deps, env, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetRewardWallet { wallet: vec![Wallet{
+            address:HumanAddr::from("reward1"),
+            portion:Decimal::from_ratio(100 as u128,100 as u128)
+        }] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let state = query_state_info(&deps).unwrap();
+
+        assert_eq!(state.reward_wallet,vec![Wallet{
+            address:HumanAddr::from("reward1"),
+            portion:Decimal::one()
+        }]);
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::UploadTokenMetadata {collection:"SteamPunk_Human".to_string(),start:0,metadata: vec![
+            TokenMetadata{ name:Some("metadata1".to_string()), ..TokenMetadata::default() },
+            TokenMetadata{ name:Some("metadata2".to_string()), ..TokenMetadata::default() }
+        ] };
+
+        
+
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let metadata = read_token_metadata(&deps.storage,"SteamPunk_Human",1).unwrap();
+        assert_eq!(metadata.name,Some("metadata2".to_string()));
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetMaximumNft { amount: Uint128(2) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.maximum_count,Uint128(2));
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.total_supply,Uint128(5));
+
+        
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetPrice { public_price: Uint128(5), private_price: Uint128(10) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.public_price,Uint128(5));
+        assert_eq!(state.private_price,Uint128(10));
+
+        
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::ChangeAdmin { address: HumanAddr::from("admin1") };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.admin,HumanAddr::from("admin1"));
+
+
+        let env = mock_env("admin1", &vec![]);
+        let msg = HandleMsg::RemoveWhiteUsers { members:vec![HumanAddr::from("white1"),HumanAddr::from("white3")], tier: None };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let members = query_white_users(&deps, None, None, None).unwrap();
+        assert_eq!(members,vec![HumanAddr::from("white2")]);
+
+
+        let env = mock_env("admin1", &vec![]);
+        let msg = HandleMsg::AddWhiteUsers { members: vec![HumanAddr::from("white1"),HumanAddr::from("white2"),HumanAddr::from("white3")], tier: None };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let members = query_white_users(&deps, None, None, None).unwrap();
+        assert_eq!(members,vec![HumanAddr::from("white2"),HumanAddr::from("white1"),HumanAddr::from("white3")]);
+
+        let members = query_white_users(&deps, Some(HumanAddr::from("white2")), Some(1), None).unwrap();
+        assert_eq!(members,vec![HumanAddr::from("white1")]);
+        let members = query_white_users(&deps, Some(HumanAddr::from("white1")), Some(5), None).unwrap();
+        assert_eq!(members,vec![HumanAddr::from("white3")]);
+        assert!(query_white_users(&deps, Some(HumanAddr::from("white4")), None, None).is_err());
+
+        let env = mock_env("admin1", &vec![]);
+        let msg = HandleMsg::RemoveWhiteUsers { members:vec![HumanAddr::from("white2")], tier: None };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let members = query_white_users(&deps, None, None, None).unwrap();
+        assert_eq!(members,vec![HumanAddr::from("white3"),HumanAddr::from("white1")]);
+        assert!(is_member(&deps.storage, "", &HumanAddr::from("white1")));
+        assert!(!is_member(&deps.storage, "", &HumanAddr::from("white2")));
+
+    }
+
+     #[test]
+    fn mint() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &coins(1000, "earth"));
+
+        let msg = InitMsg {
+            white_members : vec![HumanAddr::from("white1"),HumanAddr::from("white2")],
+            maximum_count : Uint128(1),
+            public_price : Uint128(600000),
+            private_price : Uint128(400000),
+            reward_wallet : vec![Wallet{
+                address:HumanAddr::from("reward1"),
+                portion:Decimal::from_ratio(70 as u128,100 as u128)
+            },
+            Wallet{
+                address:HumanAddr::from("reward2"),
+                portion:Decimal::from_ratio(30 as u128,100 as u128)
+            }
+            ],
+            ..init_msg()
+        };
+        
+        // we can just call .unwrap() to assert this was a success
+        let res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+        assert_eq!(1, res.messages.len());
+        
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetNftAddress { nft_address: HumanAddr::from("nft"),nft_contract_hash:"123".to_string() };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.nft_contract_hash,"123");
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.nft_address,HumanAddr::from("nft"));
+
+        let message = to_binary(&MintMsg::default()).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::UploadTokenMetadata {collection:"SteamPunk_Human".to_string(),start:0,metadata: vec![
+            TokenMetadata{
+                name:Some("name".to_string()),
+                description:Some("secret steam".to_string()),
+                attributes : Some(vec![Trait{
+                    trait_type:Some("Clothes".to_string()),
+                    value:"value".to_string(),
+                    display_type:None,
+                    max_value:None
+                }]),
+                image:Some("image".to_string()),
+                animation_url:None
+            }
+        ] };
+         let _res = handle(&mut deps, env, msg).unwrap();
+
+        let metadata = read_token_metadata(&deps.storage,"SteamPunk_Human",0).unwrap();
+        assert_eq!(metadata.name,Some("name".to_string()));
+        
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let env = mock_env("token_address", &vec![]);
+         let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
+
+        
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let user_info = query_user_info(&deps, HumanAddr::from("minter2")).unwrap();
+        let empty : Vec<String> = vec![];
+        assert_eq!(user_info.token_ids,empty);
+        
+        // let user_info = query_user_info(&deps, HumanAddr::from("white1")).unwrap();
+        // assert_eq!(user_info,vec!["token_id1".to_string()]);       
+
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
+        let _res = handle(&mut deps, env, msg).unwrap();
+       
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
+        let _res = handle(&mut deps, env, msg).unwrap();
+       
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.count,Uint128(5));
+        let status = query_minted_status(&deps, 0, 10).unwrap();
+        assert_eq!(status.minted,[true,true,true,true,true]);
+    }
+
+    #[test]
+    fn collections() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            maximum_count : Uint128(1),
+            public_price : Uint128(500000),
+            private_price : Uint128(600000),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let collections = query_private_collections(&deps).unwrap();
+        assert_eq!(collections, test_collections());
+
+        let (collection, index) = find_collection(&collections, 3).unwrap();
+        assert_eq!(collection.name, "SteamPunk_Bull");
+        assert_eq!(index, 1);
+        let (collection, index) = find_collection(&collections, 4).unwrap();
+        assert_eq!(collection.name, "SteamPunk_Robot");
+        assert_eq!(index, 0);
+        assert!(find_collection(&collections, 5).is_err());
+
+        let new_collection = Collection{
+            name:"SteamPunk_Cat".to_string(),
+            metadata_uri:"cat".to_string(),
+            image_uri:"cat".to_string(),
+            supply:Uint128(3),
+            private_metadata:None,
+            token_id_format:None,
+            metadata_format:None,
+            image_format:None
+        };
+        let env = mock_env("user", &vec![]);
+        let msg = HandleMsg::AddCollection { collection: new_collection.clone() };
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::AddCollection { collection: new_collection.clone() };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(query_collections(&deps).unwrap().len(), 4);
+        assert_eq!(query_state_info(&deps).unwrap().total_supply, Uint128(8));
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::AddCollection { collection: new_collection.clone() };
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::RemoveCollection { name: "SteamPunk_Bull".to_string() };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let collections = query_private_collections(&deps).unwrap();
+        assert_eq!(collections.len(), 3);
+        assert_eq!(query_state_info(&deps).unwrap().total_supply, Uint128(6));
+        let (collection, index) = find_collection(&collections, 3).unwrap();
+        assert_eq!(collection.name, "SteamPunk_Cat");
+        assert_eq!(index, 0);
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetCollections { collections: vec![new_collection.clone()] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(query_private_collections(&deps).unwrap(), vec![new_collection.clone()]);
+        assert_eq!(query_state_info(&deps).unwrap().total_supply, Uint128(3));
+
+        // the registry is frozen once a token is minted
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("minter"), from: HumanAddr::from("minter"), amount: Uint128(500000), msg: Binary(vec![]) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::AddCollection { collection: Collection{ name: "SteamPunk_Dog".to_string(), ..new_collection } };
+        assert!(handle(&mut deps, env, msg).is_err());
+    }
+
+    #[test]
+    fn batch_mint() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            white_members : vec![HumanAddr::from("white1")],
+            public_price : Uint128(600000),
+            private_price : Uint128(400000),
+            reward_wallet : vec![Wallet{
+                address:HumanAddr::from("reward1"),
+                portion:Decimal::from_ratio(70 as u128,100 as u128)
+            },
+            Wallet{
+                address:HumanAddr::from("reward2"),
+                portion:Decimal::from_ratio(30 as u128,100 as u128)
+            }
+            ],
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: true, public_mint: false };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // the whitelist cap counts every token of the batch
+        let message = to_binary(&MintMsg{ quantity: Some(3), ..MintMsg::default() }).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("white1"), amount: Uint128(1200000), msg: message };
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let message = to_binary(&MintMsg{ quantity: Some(2), ..MintMsg::default() }).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("white1"), amount: Uint128(800000), msg: message };
+        let res = handle(&mut deps, env, msg).unwrap();
+        // one batch mint plus one transfer per reward wallet
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(query_user_info(&deps, HumanAddr::from("white1")).unwrap().minted, Uint128(2));
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // a price that overflows for the quantity is refused, not wrapped
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetPrice { public_price: Uint128(u128::MAX / 2 + 1), private_price: Uint128(400000) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = mint_msg("minter", 2, 0);
+        let err: StdResult<HandleResponse> = Err(ContractError::PriceOverflow { price: Uint128(u128::MAX / 2 + 1), quantity: 2 }.into());
+        assert_eq!(handle(&mut deps, env, msg), err);
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetPrice { public_price: Uint128(600000), private_price: Uint128(400000) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // the price is charged per token
+        let message = to_binary(&MintMsg{ quantity: Some(3), ..MintMsg::default() }).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("minter"), from: HumanAddr::from("minter"), amount: Uint128(600000), msg: message.clone() };
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("minter"), from: HumanAddr::from("minter"), amount: Uint128(1800000), msg: message };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.count, Uint128(5));
+        let status = query_minted_status(&deps, 0, 10).unwrap();
+        assert_eq!(status.minted, [true,true,true,true,true]);
+
+        // nothing left for another batch
+        let message = to_binary(&MintMsg{ quantity: Some(1), ..MintMsg::default() }).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("minter"), from: HumanAddr::from("minter"), amount: Uint128(600000), msg: message };
+        assert!(handle(&mut deps, env, msg).is_err());
+    }
+
+    #[test]
+    fn pool_draws_every_index_once() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut drawn = vec![];
+        let mut remaining = 5;
+        for rand in vec![7, 0, 2, 1, 9]{
+            drawn.push(draw_index(&mut deps.storage, remaining, rand).unwrap());
+            remaining -= 1;
+        }
+        // position 2 goes first and is refilled with the last index
+        assert_eq!(drawn[0], 2);
+        assert_eq!(drawn[1], 0);
+        drawn.sort();
+        assert_eq!(drawn, vec![0, 1, 2, 3, 4]);
+        for position in 0..5{
+            assert_eq!(read_pool_index(&deps.storage, position), None);
+        }
+    }
+
+    #[test]
+    fn minted_bitset_pages() {
+        let mut deps = mock_dependencies(20, &[]);
+        set_minted(&mut deps.storage, 3).unwrap();
+        set_minted(&mut deps.storage, 300).unwrap();
+        set_minted(&mut deps.storage, 301).unwrap();
+
+        assert!(is_minted(&deps.storage, 3));
+        assert!(!is_minted(&deps.storage, 4));
+        assert!(!is_minted(&deps.storage, 259));
+        assert!(is_minted(&deps.storage, 300));
+        assert!(is_minted(&deps.storage, 301));
+        assert_eq!(read_minted_page(&deps.storage, 1)[5], 0b0011_0000);
+        assert_eq!(read_minted_page(&deps.storage, 7), vec![0u8; 32]);
+    }
+
+    #[test]
+    fn scheduled_phases() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            white_members : vec![HumanAddr::from("white1")],
+            maximum_count : Uint128(1),
+            public_price : Uint128(600000),
+            private_price : Uint128(400000),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let presale = Phase{
+            name:"presale".to_string(),
+            start:1000,
+            end:2000,
+            price:Uint128(100),
+            per_wallet_limit:Some(Uint128(2)),
+            whitelist_only:true,
+            tiers:vec![],
+            tiers_only:false
+        };
+        let sale = Phase{
+            name:"sale".to_string(),
+            start:2000,
+            end:3000,
+            price:Uint128(200),
+            per_wallet_limit:None,
+            whitelist_only:false,
+            tiers:vec![],
+            tiers_only:false
+        };
+
+        let env = mock_env("admin", &vec![]);
+        let mut overlapping = sale.clone();
+        overlapping.start = 1500;
+        let msg = HandleMsg::SetPhases { phases: vec![presale.clone(), overlapping] };
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetPhases { phases: vec![sale.clone(), presale.clone()] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        assert_eq!(query_active_phase(&deps, 999).unwrap(), None);
+        assert_eq!(query_active_phase(&deps, 1000).unwrap(), Some(presale.clone()));
+        assert_eq!(query_active_phase(&deps, 2000).unwrap(), Some(sale.clone()));
+        assert_eq!(query_active_phase(&deps, 3000).unwrap(), None);
+
+        let (env, msg) = mint_msg("white1", 1, 100);
+        assert!(handle(&mut deps, at_time(env, 500), msg).is_err());
+
+        let (env, msg) = mint_msg("minter", 1, 100);
+        assert!(handle(&mut deps, at_time(env, 1500), msg).is_err());
+
+        let (env, msg) = mint_msg("white1", 3, 300);
+        assert!(handle(&mut deps, at_time(env, 1500), msg).is_err());
+
+        let (env, msg) = mint_msg("white1", 2, 200);
+        let _res = handle(&mut deps, at_time(env, 1500), msg).unwrap();
+
+        let (env, msg) = mint_msg("minter", 3, 600);
+        let _res = handle(&mut deps, at_time(env, 2500), msg).unwrap();
+
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.count, Uint128(5));
+    }
+
+    #[test]
+    fn merkle_whitelist() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            maximum_count : Uint128(5),
+            public_price : Uint128(600000),
+            private_price : Uint128(400000),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        // two leaf tree: "og" may mint 2 tokens, "white1" has no allocation
+        let og = leaf_hash("og", Some(2));
+        let white = leaf_hash("white1", None);
+        let pair = if og <= white { [og, white].concat() } else { [white, og].concat() };
+        let root = sha_256(&pair);
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetMerkleRoot { root: Some(Binary(root.to_vec())) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: true, public_mint: false };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let receive = |sender: &str, quantity: u32, mint_msg: MintMsg| {
+            HandleMsg::Receive {
+                sender: HumanAddr::from(sender),
+                from: HumanAddr::from(sender),
+                amount: Uint128(400000 * quantity as u128),
+                msg: to_binary(&MintMsg{ quantity: Some(quantity), ..mint_msg }).unwrap()
+            }
+        };
+
+        let env = mock_env("token_address", &vec![]);
+        let msg = receive("white1", 1, MintMsg::default());
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("token_address", &vec![]);
+        let msg = receive("white1", 1, MintMsg{ proof: Some(vec![Binary(og.to_vec())]), ..MintMsg::default() });
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // the allocation must match the leaf and caps the wallet
+        let env = mock_env("token_address", &vec![]);
+        let msg = receive("og", 1, MintMsg{ proof: Some(vec![Binary(white.to_vec())]), allocation: Some(3), ..MintMsg::default() });
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("token_address", &vec![]);
+        let msg = receive("og", 3, MintMsg{ proof: Some(vec![Binary(white.to_vec())]), allocation: Some(2), ..MintMsg::default() });
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("token_address", &vec![]);
+        let msg = receive("og", 2, MintMsg{ proof: Some(vec![Binary(white.to_vec())]), allocation: Some(2), ..MintMsg::default() });
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(query_user_info(&deps, HumanAddr::from("og")).unwrap().minted, Uint128(2));
+
+        // a new phase does not renew the allocation
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetPhases { phases: vec![Phase{
+            name:"second".to_string(),
+            start:0,
+            end:u64::MAX,
+            price:Uint128(400000),
+            per_wallet_limit:None,
+            whitelist_only:true,
+            tiers:vec![],
+            tiers_only:false
+        }] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = receive("og", 1, MintMsg{ proof: Some(vec![Binary(white.to_vec())]), allocation: Some(2), ..MintMsg::default() });
+        let err: StdResult<HandleResponse> = Err(ContractError::MintLimitReached { remaining: Uint128(0) }.into());
+        assert_eq!(handle(&mut deps, env, msg), err);
+    }
+
+    #[test]
+    fn whitelist_tiers() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            white_members : vec![HumanAddr::from("white1")],
+            maximum_count : Uint128(5),
+            public_price : Uint128(600000),
+            private_price : Uint128(400000),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let og = Tier{
+            name:"og".to_string(),
+            price:Uint128(50),
+            per_wallet_cap:Uint128(2),
+            allocation:Uint128(3)
+        };
+        let partner = Tier{
+            name:"partner".to_string(),
+            price:Uint128(80),
+            per_wallet_cap:Uint128(1),
+            allocation:Uint128(1)
+        };
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetTiers { tiers: vec![og.clone(), partner.clone()] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetTiers { tiers: vec![Tier{ name: "raffle/launch".to_string(), ..og.clone() }] };
+        let err: StdResult<HandleResponse> = Err(ContractError::ReservedName { kind: "tier".to_string(), name: "raffle/launch".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, msg), err);
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::AddWhiteUsers { members: vec![HumanAddr::from("og1")], tier: Some("vip".to_string()) };
+        assert!(handle(&mut deps, env, msg).is_err());
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::AddWhiteUsers { members: vec![HumanAddr::from("og1"),HumanAddr::from("og2")], tier: Some("og".to_string()) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::AddWhiteUsers { members: vec![HumanAddr::from("partner1")], tier: Some("partner".to_string()) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert!(is_member(&deps.storage, "og", &HumanAddr::from("og1")));
+        assert!(!is_member(&deps.storage, "", &HumanAddr::from("og1")));
+
+        // og members get in first, partners join the regular presale
+        let phases = vec![
+            Phase{
+                name:"og".to_string(),
+                start:1000,
+                end:2000,
+                price:Uint128(100),
+                per_wallet_limit:None,
+                whitelist_only:true,
+                tiers:vec!["og".to_string()],
+                tiers_only:true
+            },
+            Phase{
+                name:"presale".to_string(),
+                start:2000,
+                end:3000,
+                price:Uint128(100),
+                per_wallet_limit:None,
+                whitelist_only:true,
+                tiers:vec!["og".to_string(),"partner".to_string()],
+                tiers_only:false
+            }
+        ];
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetPhases { phases };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let (env, msg) = mint_msg("white1", 1, 100);
+        assert!(handle(&mut deps, at_time(env, 1500), msg).is_err());
+        let (env, msg) = mint_msg("partner1", 1, 80);
+        assert!(handle(&mut deps, at_time(env, 1500), msg).is_err());
+
+        // og members pay the tier price, not the phase price
+        let (env, msg) = mint_msg("og1", 2, 200);
+        assert!(handle(&mut deps, at_time(env, 1500), msg).is_err());
+        let (env, msg) = mint_msg("og1", 2, 100);
+        let _res = handle(&mut deps, at_time(env, 1500), msg).unwrap();
+
+        // per wallet cap, then the tier's total allocation
+        let (env, msg) = mint_msg("og1", 1, 50);
+        let err = handle(&mut deps, at_time(env, 1500), msg).unwrap_err();
+        assert_eq!(err, ContractError::MintLimitReached { remaining: Uint128(0) }.into());
+        let (env, msg) = mint_msg("og2", 2, 100);
+        assert!(handle(&mut deps, at_time(env, 1500), msg).is_err());
+        let (env, msg) = mint_msg("og2", 1, 50);
+        let _res = handle(&mut deps, at_time(env, 1500), msg).unwrap();
+
+        let (env, msg) = mint_msg("partner1", 1, 80);
+        let _res = handle(&mut deps, at_time(env, 2500), msg).unwrap();
+        let (env, msg) = mint_msg("white1", 1, 100);
+        let _res = handle(&mut deps, at_time(env, 2500), msg).unwrap();
+
+        let tiers = query_tiers(&deps).unwrap();
+        assert_eq!(tiers, vec![
+            TierInfo{ tier: og.clone(), minted: Uint128(3) },
+            TierInfo{ tier: partner.clone(), minted: Uint128(1) }
+        ]);
+        let state = query_state_info(&deps).unwrap();
+        assert_eq!(state.count, Uint128(5));
+
+        // a removed tier's name can not come back with its old members and counts
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetTiers { tiers: vec![og.clone()] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::AddWhiteUsers { members: vec![HumanAddr::from("partner2")], tier: Some("partner".to_string()) };
+        assert!(handle(&mut deps, env, msg).is_err());
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetTiers { tiers: vec![og.clone(), partner] };
+        let err: StdResult<HandleResponse> = Err(ContractError::RetiredTier { name: "partner".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, msg), err);
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetTiers { tiers: vec![og.clone(), Tier{ name: "partner2".to_string(), ..og }] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(query_tiers(&deps).unwrap()[1].minted, Uint128(0));
+    }
+
+    #[test]
+    fn wallet_limits() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            white_members : vec![HumanAddr::from("white1")],
+            public_price : Uint128(200),
+            private_price : Uint128(100),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let env = mock_env("minter", &vec![]);
+        let msg = HandleMsg::SetWalletLimits { public_maximum_count: Some(Uint128(2)), wallet_maximum_count: Some(Uint128(3)) };
+        assert!(handle(&mut deps, env, msg).is_err());
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetWalletLimits { public_maximum_count: Some(Uint128(2)), wallet_maximum_count: Some(Uint128(3)) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: true, public_mint: false };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = mint_msg("white1", 2, 200);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // the public cap applies per wallet
+        let (env, msg) = mint_msg("minter", 3, 600);
+        assert!(handle(&mut deps, env, msg).is_err());
+        let (env, msg) = mint_msg("minter", 2, 400);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // white1 is within the public cap but has used 2 of 3 overall
+        let (env, msg) = mint_msg("white1", 2, 400);
+        assert!(handle(&mut deps, env, msg).is_err());
+        let (env, msg) = mint_msg("white1", 1, 200);
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        let user_info = query_user_info(&deps, HumanAddr::from("white1")).unwrap();
+        assert_eq!(user_info.token_ids.len(), 3);
+        assert_eq!(user_info.minted, Uint128(3));
+        assert_eq!(user_info.remaining, Some(Uint128(0)));
+        let user_info = query_user_info(&deps, HumanAddr::from("minter")).unwrap();
+        assert_eq!(user_info.remaining, Some(Uint128(1)));
+    }
+
+    #[test]
+    fn reward_claims() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            reward_wallet : vec![Wallet{
+                address:HumanAddr::from("reward1"),
+                portion:Decimal::from_ratio(70 as u128,100 as u128)
+            },
+            Wallet{
+                address:HumanAddr::from("reward2"),
+                portion:Decimal::from_ratio(30 as u128,100 as u128)
+            }
+            ],
+            push_rewards : Some(false),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        for address in &["reward1", "reward2", "reward3"]{
+            let env = mock_env(*address, &vec![]);
+            let msg = HandleMsg::SetViewingKey { key: "key".to_string(), padding: None };
+            let _res = handle(&mut deps, env, msg).unwrap();
+        }
+        let pending = |deps: &Extern<_, _, _>, address: &str| -> Uint128 {
+            from_binary(&query(deps, QueryMsg::GetPendingRewards { address: HumanAddr::from(address), key: "key".to_string() }).unwrap()).unwrap()
+        };
+
+        let (env, msg) = mint_msg("minter", 1, 1000);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(pending(&deps, "reward1"), Uint128(700));
+        assert_eq!(pending(&deps, "reward2"), Uint128(300));
+
+        // a new split leaves earned balances alone
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetRewardWallet { wallet: vec![Wallet{ address: HumanAddr::from("reward3"), portion: Decimal::one() }] };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = mint_msg("minter", 1, 1000);
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(pending(&deps, "reward1"), Uint128(700));
+        assert_eq!(pending(&deps, "reward3"), Uint128(1000));
+
+        let env = mock_env("reward1", &vec![]);
+        let res = handle(&mut deps, env, HandleMsg::ClaimRewards {}).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(pending(&deps, "reward1"), Uint128(0));
+        let env = mock_env("reward1", &vec![]);
+        assert!(handle(&mut deps, env, HandleMsg::ClaimRewards {}).is_err());
+
+        // push mode sends the share with the mint
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetRewardMode { push: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = mint_msg("minter", 1, 1000);
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(pending(&deps, "reward3"), Uint128(1000));
+    }
+
+    #[test]
+    fn reward_dust() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+
+        let msg = InitMsg {
+            public_price : Uint128(1001),
+            reward_wallet : vec![Wallet{
+                address:HumanAddr::from("reward1"),
+                portion:Decimal::from_ratio(70 as u128,100 as u128)
+            },
+            Wallet{
+                address:HumanAddr::from("reward2"),
+                portion:Decimal::from_ratio(30 as u128,100 as u128)
+            }
+            ],
+            push_rewards : Some(false),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+
+        // 700.7 + 300.3 truncates to 1000, leaving 1 behind
+        let (env, msg) = mint_msg("minter", 1, 1001);
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(read_pending_rewards(&deps.storage, "reward1"), Uint128(700));
+        assert_eq!(read_pending_rewards(&deps.storage, "reward2"), Uint128(300));
+        assert_eq!(read_dust(&deps.storage), Uint128(1));
+
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetDustWallet { address: Some(HumanAddr::from("reward1")) };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = mint_msg("minter", 1, 1001);
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(read_pending_rewards(&deps.storage, "reward1"), Uint128(1401));
+        assert_eq!(read_dust(&deps.storage), Uint128(1));
+
+        let env = mock_env("minter", &vec![]);
+        assert!(handle(&mut deps, env, HandleMsg::SweepDust { recipient: None }).is_err());
+        let env = mock_env("admin", &vec![]);
+        let res = handle(&mut deps, env, HandleMsg::SweepDust { recipient: Some(HumanAddr::from("treasury")) }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(read_dust(&deps.storage), Uint128(0));
+        let env = mock_env("admin", &vec![]);
+        assert!(handle(&mut deps, env, HandleMsg::SweepDust { recipient: None }).is_err());
+    }
 
-fn query_metadata<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,key:String) -> StdResult<Vec<String>> {
-    let metadata = read_url_info(&deps.storage,&key).unwrap();
-    Ok(metadata)
-}
+    #[test]
+    fn viewing_keys() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
 
-fn query_white_users<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Vec<HumanAddr>> {
-    let members = read_members(&deps.storage).load()?;
-    Ok(members)
-}
+        let msg = init_msg();
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
 
-fn query_random<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<u16> {
-    let rand = read_rand(&deps.storage).load()?;
-    Ok(rand)
-}
+        let env = mock_env("admin", &vec![]);
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("minter"), from: HumanAddr::from("minter"), amount: Uint128(1000), msg: Binary(vec![]) };
+        let _res = handle(&mut deps, env, msg).unwrap();
 
+        let user_info = |deps: &Extern<_, _, _>, key: &str| {
+            query(deps, QueryMsg::GetUserInfo { address: HumanAddr::from("minter"), key: key.to_string() })
+        };
+        let invalid: StdResult<Binary> = Err(ContractError::InvalidViewingKey {}.into());
+        assert_eq!(user_info(&deps, ""), invalid);
+
+        let env = mock_env("minter", &vec![]);
+        let msg = HandleMsg::CreateViewingKey { entropy: "dice".to_string(), padding: None };
+        let res = handle(&mut deps, env, msg).unwrap();
+        let key = match from_binary(&res.data.unwrap()).unwrap(){
+            HandleAnswer::CreateViewingKey { key } => key
+        };
+        let info: UserInfoResponse = from_binary(&user_info(&deps, &key.0).unwrap()).unwrap();
+        assert_eq!(info.minted, Uint128(1));
+        assert_eq!(user_info(&deps, "wrong"), invalid);
+
+        // another wallet's key does not open minter's history
+        let env = mock_env("other", &vec![]);
+        let msg = HandleMsg::SetViewingKey { key: "other_key".to_string(), padding: None };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(user_info(&deps, "other_key"), invalid);
 
-fn query_user_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>,address:HumanAddr) -> StdResult<Vec<String>> {
-    let user_info  = read_user_info(&deps.storage,&address.as_str());
-    if user_info == None{
-        Ok(vec![])
-    }
-    else{
-    Ok(user_info.unwrap())
+        let env = mock_env("minter", &vec![]);
+        let msg = HandleMsg::SetViewingKey { key: "my_key".to_string(), padding: None };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert!(user_info(&deps, "my_key").is_ok());
+        assert_eq!(user_info(&deps, &key.0), invalid);
     }
-}
-
-pub fn new_entropy(env: &Env, seed: &[u8], entropy: &[u8]) -> [u8; 32] {
-    // 16 here represents the lengths in bytes of the block height and time.
-    let entropy_len = 16 + env.message.sender.len() + entropy.len();
-    let mut rng_entropy = Vec::with_capacity(entropy_len);
-    rng_entropy.extend_from_slice(&env.block.height.to_be_bytes());
-    rng_entropy.extend_from_slice(&env.block.time.to_be_bytes());
-    rng_entropy.extend_from_slice(&env.message.sender.0.as_bytes());
-    rng_entropy.extend_from_slice(entropy);
 
-    let mut rng = Prng::new(seed, &rng_entropy);
+    #[test]
+    fn query_permits() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
 
-    rng.rand_bytes()
-}
+        let msg = init_msg();
+        let _res = init(&mut deps, env.clone(), msg).unwrap();
+        reveal_seed(&mut deps);
+
+        let permit = Permit{
+            params: permit::PermitParams{
+                allowed_tokens: vec![HumanAddr::from("other_contract")],
+                permit_name: "history".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::History]
+            },
+            signature: permit::PermitSignature{
+                pub_key: permit::PubKey{
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary(vec![2u8; 33])
+                },
+                signature: Binary(vec![1u8; 10])
+            }
+        };
+        let with_permit = |deps: &Extern<_, _, _>, permit: &Permit| {
+            query(deps, QueryMsg::WithPermit { permit: permit.clone(), query: QueryWithPermit::UserInfo {} })
+        };
 
+        let err: StdResult<Binary> = Err(ContractError::PermitContractMismatch {}.into());
+        assert_eq!(with_permit(&deps, &permit), err);
 
-#[cfg(test)]
-mod tests {
+        let mut permit = permit;
+        permit.params.allowed_tokens.push(env.contract.address.clone());
+        let err: StdResult<Binary> = Err(ContractError::InvalidPermitSignature {}.into());
+        assert_eq!(with_permit(&deps, &permit), err);
 
+        let env = mock_env("minter", &vec![]);
+        let msg = HandleMsg::RevokePermit { permit_name: "history".to_string(), padding: None };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert!(is_permit_revoked(&deps.storage, "minter", "history"));
+        assert!(!is_permit_revoked(&deps.storage, "minter", "balance"));
+        assert!(!is_permit_revoked(&deps.storage, "other", "history"));
+
+        assert!(permit.check_permission(Permission::History));
+        assert!(!permit.check_permission(Permission::Balance));
+        permit.params.permissions = vec![Permission::Owner];
+        assert!(permit.check_permission(Permission::Balance));
+
+        // a permit signed offline by a wallet that minted and earns rewards
+        let admin = mock_env("admin", &vec![]);
+        let permit = Permit{
+            params: permit::PermitParams{
+                allowed_tokens: vec![admin.contract.address.clone()],
+                permit_name: "mint_permit".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::History, Permission::Balance]
+            },
+            signature: permit::PermitSignature{
+                pub_key: permit::PubKey{
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("AouBQmBFygES2KFu7Zyo7qzcZ+abEPvMMTIjrQm8UEEr").unwrap()
+                },
+                signature: Binary::from_base64("R5na5xUUW2FPmPoIJc6z9GPy3UG4Bf5U6eJB7BGjWmEPzEUCuB6wq/FzBoakqvn6VnHiQjXJSi3kxeaspCrzwA==").unwrap()
+            }
+        };
+        let signer = deps.api.human_address(&permit::pubkey_to_canonical(permit.signature.pub_key.value.as_slice())).unwrap();
 
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env};
-    use cosmwasm_std::{coins};
+        let msg = HandleMsg::SetRewardWallet { wallet: vec![Wallet{ address: signer.clone(), portion: Decimal::one() }] };
+        let _res = handle(&mut deps, admin.clone(), msg).unwrap();
+        let _res = handle(&mut deps, admin.clone(), HandleMsg::SetRewardMode { push: false }).unwrap();
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, admin, msg).unwrap();
+        let (env, msg) = mint_msg(signer.as_str(), 1, 1000);
+        let _res = handle(&mut deps, env, msg).unwrap();
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(20, &[]);
-        let env = mock_env("creator", &coins(1000, "earth"));
+        let info: UserInfoResponse = from_binary(&with_permit(&deps, &permit).unwrap()).unwrap();
+        assert_eq!(info.token_ids.len(), 1);
+        assert_eq!(info.minted, Uint128(1));
+        let query_msg = QueryMsg::WithPermit { permit: permit.clone(), query: QueryWithPermit::PendingRewards {} };
+        let pending: Uint128 = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        assert_eq!(pending, Uint128(1000));
 
-        let msg = InitMsg {
-             white_members: vec![HumanAddr::from("white1"),HumanAddr::from("white2")],
-             admin : HumanAddr::from("admin"),
-             total_supply : Uint128(5),
-             maximum_count :Uint128(1),
-             public_price : Uint128(500000),
-             private_price:Uint128(600000) ,
-             reward_wallet : vec![Wallet{
-                 address:HumanAddr::from("reward1"),
-                 portion:Decimal::from_ratio(7 as u128,100 as u128)
-             },
-             Wallet{
-                 address:HumanAddr::from("reward2"),
-                 portion:Decimal::from_ratio(3 as u128,100 as u128)
-             }
-             ],
-             token_address:HumanAddr::from("token_address"),
-             token_contract_hash :"token_hash".to_string(),
-             check_minted : vec![true,true,true,true,true],
-             human_image:"human".to_string(),
-             human_metadata:"human".to_string(),
-             robot_image:"robot".to_string(),
-             robot_metadata:"robot".to_string(),
-             bull_image:"bull".to_string(),
-             bull_metadata:"bull".to_string()
-            };
-        
-        // we can just call .unwrap() to assert this was a success
-        let res = init(&mut deps, env.clone(), msg).unwrap();
-        assert_eq!(0, res.messages.len());
-      
-       
+        let env = mock_env(signer, &vec![]);
+        let msg = HandleMsg::RevokePermit { permit_name: "mint_permit".to_string(), padding: None };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let err: StdResult<Binary> = Err(ContractError::PermitRevoked { permit_name: "mint_permit".to_string() }.into());
+        assert_eq!(with_permit(&deps, &permit), err);
     }
 
     #[test]
-    fn test_state() {
-         let mut deps = mock_dependencies(20, &[]);
+    fn sale_info_queries() {
+        let mut deps = mock_dependencies(20, &[]);
         let env = mock_env("creator", &vec![]);
 
-        let msg = InitMsg {
-             white_members: vec![HumanAddr::from("white1"),HumanAddr::from("white2")],
-             admin : HumanAddr::from("admin"),
-             total_supply : Uint128(5),
-             maximum_count :Uint128(1),
-             public_price : Uint128(500000),
-             private_price:Uint128(600000) ,
-             reward_wallet : vec![Wallet{
-                 address:HumanAddr::from("reward1"),
-                 portion:Decimal::from_ratio(7 as u128,100 as u128)
-             },
-             Wallet{
-                 address:HumanAddr::from("reward2"),
-                 portion:Decimal::from_ratio(3 as u128,100 as u128)
-             }
-             ],
-              token_address:HumanAddr::from("token_address"),
-              token_contract_hash :"token_hash".to_string(),
-                check_minted : vec![true,true,true,true,true],
-           
-             human_image:"human".to_string(),
-             human_metadata:"human".to_string(),
-             robot_image:"robot".to_string(),
-             robot_metadata:"robot".to_string(),
-             bull_image:"bull".to_string(),
-             bull_metadata:"bull".to_string()
-            };
-        
-        let res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-        
+        let msg = init_msg();
+        let _res = init(&mut deps, env, msg).unwrap();
+        reveal_seed(&mut deps);
+
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::SetRewardWallet { wallet: vec![Wallet{
-            address:HumanAddr::from("reward1"),
-            portion:Decimal::from_ratio(100 as u128,100 as u128)
-        }] };
+        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let msg = HandleMsg::Receive { sender: HumanAddr::from("minter"), from: HumanAddr::from("minter"), amount: Uint128(1000), msg: Binary(vec![]) };
         let _res = handle(&mut deps, env, msg).unwrap();
-        let state = query_state_info(&deps).unwrap();
 
-        assert_eq!(state.reward_wallet,vec![Wallet{
-            address:HumanAddr::from("reward1"),
-            portion:Decimal::one()
-        }]);
+        let sale: SaleInfoResponse = from_binary(&query(&deps, QueryMsg::GetSaleInfo { time: 0 }).unwrap()).unwrap();
+        assert_eq!(sale.minted, Uint128(1));
+        assert_eq!(sale.total_supply, Uint128(5));
+        assert_eq!(sale.public_price, Uint128(1000));
+        assert_eq!(sale.phase.unwrap().name, "public");
 
-        let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::AddMetaData {key:"0".to_string(),metadata: vec![
-            "metadata1".to_string(),
-            "metadata2".to_string()
-        ] };
+        for (address, key) in &[("admin", "admin_key"), ("minter", "minter_key")]{
+            let env = mock_env(*address, &vec![]);
+            let msg = HandleMsg::SetViewingKey { key: key.to_string(), padding: None };
+            let _res = handle(&mut deps, env, msg).unwrap();
+        }
 
-        
+        let invalid: StdResult<Binary> = Err(ContractError::InvalidViewingKey {}.into());
+        assert_eq!(query(&deps, QueryMsg::GetConfig { key: "minter_key".to_string() }), invalid);
+        assert_eq!(query(&deps, QueryMsg::GetMintedStatus { start: 0, limit: 5, key: "minter_key".to_string() }), invalid);
 
-        let _res = handle(&mut deps, env, msg).unwrap();
-        let metadata = query_metadata(&deps,"0".to_string()).unwrap();
-        assert_eq!(metadata,vec![
-            "metadata1".to_string(),
-            "metadata2".to_string()
-        ] );
+        let state: State = from_binary(&query(&deps, QueryMsg::GetConfig { key: "admin_key".to_string() }).unwrap()).unwrap();
+        assert_eq!(state.reward_wallet[0].address, HumanAddr::from("reward1"));
+        let status: MintedStatusResponse = from_binary(&query(&deps, QueryMsg::GetMintedStatus { start: 0, limit: 5, key: "admin_key".to_string() }).unwrap()).unwrap();
+        assert_eq!(status.minted.iter().filter(|minted| **minted).count(), 1);
+    }
 
+    #[test]
+    fn commit_reveal_seed() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let err: StdResult<InitResponse> = Err(ContractError::InvalidSeedCommitment {}.into());
+        assert_eq!(init(&mut deps, env, InitMsg { seed_commitment: Binary(b"secret".to_vec()), ..init_msg() }), err);
+
+        let setup = || {
+            let mut deps = mock_dependencies(20, &[]);
+            let env = mock_env("creator", &vec![]);
+            let msg = init_msg();
+            let _res = init(&mut deps, env, msg).unwrap();
+            let env = mock_env("admin", &vec![]);
+            let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
+            let _res = handle(&mut deps, env, msg).unwrap();
+            deps
+        };
+        let mut deps = setup();
+        let (env, msg) = receive_msg("minter", 1000, MintMsg{ entropy: Some("dice".to_string()), ..MintMsg::default() });
+        let err: StdResult<HandleResponse> = Err(ContractError::SeedNotRevealed {}.into());
+        assert_eq!(handle(&mut deps, env, msg), err);
+
+        let env = mock_env("minter", &vec![]);
+        assert!(handle(&mut deps, env, HandleMsg::RevealSeed { seed: Binary(b"secret".to_vec()) }).is_err());
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::SetMaximumNft { amount: Uint128(2) };
+        let err: StdResult<HandleResponse> = Err(ContractError::SeedMismatch {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::RevealSeed { seed: Binary(b"guess".to_vec()) }), err);
+        reveal_seed(&mut deps);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::SeedAlreadyRevealed {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::RevealSeed { seed: Binary(b"secret".to_vec()) }), err);
+
+        let (env, msg) = receive_msg("minter", 1000, MintMsg{ entropy: Some("dice".to_string()), ..MintMsg::default() });
         let _res = handle(&mut deps, env, msg).unwrap();
 
-        let state = query_state_info(&deps).unwrap();
-        assert_eq!(state.maximum_count,Uint128(2));
+        // anyone can check the seed against the commitment once the sale is over
+        let provenance = |deps: &Extern<_, _, _>| -> ProvenanceResponse { from_binary(&query(deps, QueryMsg::GetProvenance {  }).unwrap()).unwrap() };
+        assert_eq!(provenance(&deps).seed_commitment, Binary(sha_256(b"secret").to_vec()));
+        assert_eq!(provenance(&deps).mint_seed, None);
+        for minter in ["second", "third"].iter(){
+            let (env, msg) = mint_msg(minter, 2, 2000);
+            let _res = handle(&mut deps, env, msg).unwrap();
+        }
+        assert_eq!(provenance(&deps).mint_seed, Some(Binary(b"secret".to_vec())));
+
+        // the same block and sender draw a different token with other entropy
+        let mut other = setup();
+        reveal_seed(&mut other);
+        let (env, msg) = receive_msg("minter", 1000, MintMsg{ entropy: Some("coin".to_string()), ..MintMsg::default() });
+        let _res = handle(&mut other, env, msg).unwrap();
+        assert_ne!(query_user_info(&deps, HumanAddr::from("minter")).unwrap().token_ids, query_user_info(&other, HumanAddr::from("minter")).unwrap().token_ids);
 
+        // closing the sale publishes the seed as well
+        assert_eq!(provenance(&other).mint_seed, None);
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::SetTotalSupply { amount: Uint128(100) };
-        let _res = handle(&mut deps, env, msg).unwrap();
+        let _res = handle(&mut other, env, HandleMsg::CloseSale {  }).unwrap();
+        assert_eq!(provenance(&other).mint_seed, Some(Binary(b"secret".to_vec())));
+    }
 
-        let state = query_state_info(&deps).unwrap();
-        assert_eq!(state.total_supply,Uint128(100));
+    #[test]
+    fn delayed_reveal() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let msg = init_msg();
+        let _res = init(&mut deps, env, msg).unwrap();
+        let env = mock_env("minter", &vec![]);
+        assert!(handle(&mut deps, env, HandleMsg::SetPlaceholder { uri: Some("hidden".to_string()) }).is_err());
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetPlaceholder { uri: Some("hidden".to_string()) }).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetSaleFlag { private_mint: false, public_mint: true }).unwrap();
 
-        
+        // no mint seed is needed, tokens are numbered in mint order
+        let (env, msg) = mint_msg("minter", 2, 2000);
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(query_user_info(&deps, HumanAddr::from("minter")).unwrap().token_ids, vec!["SteamPunk_Human.0".to_string(), "SteamPunk_Human.1".to_string()]);
 
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::SetPrice { public_price: Uint128(5), private_price: Uint128(10) };
+        let err: StdResult<HandleResponse> = Err(ContractError::MintingStarted {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::SetPlaceholder { uri: None }), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::NothingToReveal {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::ContinueReveal {  }), err);
+
+        // the offset is only drawn once nothing is left to mint
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::SaleNotOver { remaining: Uint128(3) }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::Reveal { seed: Binary(b"art".to_vec()) }), err);
+        let (env, msg) = mint_msg("late", 2, 2000);
         let _res = handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = mint_msg("last", 1, 1000);
+        let _res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(query_user_info(&deps, HumanAddr::from("last")).unwrap().token_ids, vec!["SteamPunk_Robot.0".to_string()]);
 
-        let state = query_state_info(&deps).unwrap();
-        assert_eq!(state.public_price,Uint128(5));
-        assert_eq!(state.private_price,Uint128(10));
+        let env = mock_env("admin", &vec![]);
+        let res = handle(&mut deps, env, HandleMsg::Reveal { seed: Binary(b"art".to_vec()) }).unwrap();
+        assert_eq!(res.messages.len(), 5);
+        let reveal = read_reveal(&deps.storage).load().unwrap();
+        assert_eq!((reveal.updated, reveal.target), (5, 5));
+        assert!(reveal.offset < 5);
+        assert_eq!(revealed_index(&State { total_supply: Uint128(0), ..config_read(&deps.storage).load().unwrap() }, &reveal, 0), Err(ContractError::NothingToReveal {}.into()));
 
-        
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::AlreadyRevealed {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::Reveal { seed: Binary(b"art".to_vec()) }), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::NothingToReveal {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::ContinueReveal {  }), err);
+
+        let collections = read_collections(&deps.storage).load().unwrap();
+        let state = config_read(&deps.storage).load().unwrap();
+        let (_, token_url, _) = token_info(&collections, ((2 + reveal.offset) % 5) as u128).unwrap();
+        assert_eq!(revealed_metadata(&deps.storage, &collections, &state, &reveal, 2).unwrap().extension.unwrap().external_url, Some(token_url));
+    }
 
+    #[test]
+    fn closed_sale_reveal() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let mut collections = test_collections();
+        collections[0].supply = Uint128(30);
+        collections[1].supply = Uint128(30);
+        let msg = InitMsg {
+            collections,
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::ChangeAdmin { address: HumanAddr::from("admin1") };
+        let _res = handle(&mut deps, env, HandleMsg::SetPlaceholder { uri: Some("hidden".to_string()) }).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetSaleFlag { private_mint: false, public_mint: true }).unwrap();
+        let (env, msg) = mint_msg("minter", 55, 55000);
         let _res = handle(&mut deps, env, msg).unwrap();
 
-        let state = query_state_info(&deps).unwrap();
-        assert_eq!(state.admin,HumanAddr::from("admin1"));
+        // 6 tokens are left unsold, closing the sale lets the admin reveal the rest
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::SaleNotOver { remaining: Uint128(6) }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::Reveal { seed: Binary(b"art".to_vec()) }), err);
+        let env = mock_env("minter", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::Unauthorized {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::CloseSale {  }), err);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::CloseSale {  }).unwrap();
+        assert!(query_sale_info(&deps, 0).unwrap().closed);
+        let (env, msg) = mint_msg("late", 1, 1000);
+        let err: StdResult<HandleResponse> = Err(ContractError::SaleClosed {}.into());
+        assert_eq!(handle(&mut deps, env, msg), err);
 
+        // more tokens than one batch holds
+        let env = mock_env("admin", &vec![]);
+        let res = handle(&mut deps, env, HandleMsg::Reveal { seed: Binary(b"art".to_vec()) }).unwrap();
+        assert_eq!(res.messages.len(), 50);
+        assert_eq!(res.log, vec![log("revealed", 50), log("remaining", 5)]);
+        let env = mock_env("admin", &vec![]);
+        let res = handle(&mut deps, env, HandleMsg::ContinueReveal {  }).unwrap();
+        assert_eq!(res.messages.len(), 5);
+        assert_eq!(res.log, vec![log("revealed", 55), log("remaining", 0)]);
+        let update = match &res.messages[4]{
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => String::from_utf8(msg.0.clone()).unwrap(),
+            _ => panic!("expected a metadata update")
+        };
+        assert!(update.contains(r#""token_id":"SteamPunk_Bull.24""#));
 
-        let env = mock_env("admin1", &vec![]);
-        let msg = HandleMsg::SetWhiteUsers { members:vec![HumanAddr::from("white1")] };
-        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::NothingToReveal {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::ContinueReveal {  }), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::AlreadyRevealed {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::Reveal { seed: Binary(b"art".to_vec()) }), err);
+    }
 
-        let members = query_white_users(&deps).unwrap();
-        assert_eq!(members,vec![HumanAddr::from("white1")]);
+    #[test]
+    fn provenance() {
+        let images = ["human0", "human1", "bull0", "bull1", "robot0"];
+        let provenance_hash = sha_256(&images.iter().flat_map(|image| sha_256(image.as_bytes()).to_vec()).collect::<Vec<u8>>());
 
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let err: StdResult<InitResponse> = Err(ContractError::InvalidProvenanceHash {}.into());
+        assert_eq!(init(&mut deps, env, InitMsg { provenance_hash: Binary(b"images".to_vec()), ..init_msg() }), err);
 
-        let env = mock_env("admin1", &vec![]);
-        let msg = HandleMsg::AddWhiteUser { member: HumanAddr::from("white2") };
-        let _res = handle(&mut deps, env, msg).unwrap();
+        let env = mock_env("creator", &vec![]);
+        let _res = init(&mut deps, env, InitMsg { provenance_hash: Binary(provenance_hash.to_vec()), ..init_msg() }).unwrap();
+        let res: ProvenanceResponse = from_binary(&query(&deps, QueryMsg::GetProvenance {  }).unwrap()).unwrap();
+        assert_eq!(res.provenance_hash, Binary(provenance_hash.to_vec()));
+        assert_eq!(res.starting_index, None);
 
-        let members = query_white_users(&deps).unwrap();
-        assert_eq!(members,vec![HumanAddr::from("white1"),HumanAddr::from("white2")]);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetPlaceholder { uri: Some("hidden".to_string()) }).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetSaleFlag { private_mint: false, public_mint: true }).unwrap();
+        for (minter, quantity) in [("first", 2), ("second", 2), ("third", 1)].iter(){
+            let env = mock_env("token_address", &vec![]);
+            let _res = handle(&mut deps, env, HandleMsg::Receive {
+                sender: HumanAddr::from(*minter),
+                from: HumanAddr::from(*minter),
+                amount: Uint128(1000 * *quantity as u128),
+                msg: to_binary(&MintMsg{ quantity: Some(*quantity), ..MintMsg::default() }).unwrap()
+            }).unwrap();
+        }
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::Reveal { seed: Binary(b"art".to_vec()) }).unwrap();
+        let res: ProvenanceResponse = from_binary(&query(&deps, QueryMsg::GetProvenance {  }).unwrap()).unwrap();
+        let starting_index = res.starting_index.unwrap();
+        assert_eq!(starting_index, read_reveal(&deps.storage).load().unwrap().offset);
+
+        // token 0 shows the image at the starting index and the mapping wraps around
+        let collections = read_collections(&deps.storage).load().unwrap();
+        let state = config_read(&deps.storage).load().unwrap();
+        let reveal = read_reveal(&deps.storage).load().unwrap();
+        let image = |id: u32| revealed_metadata(&deps.storage, &collections, &state, &reveal, id).unwrap().extension.unwrap().image.unwrap();
+        let (_, _, first) = token_info(&collections, starting_index as u128).unwrap();
+        assert_eq!(image(0), first);
+        assert_eq!(image(5 - starting_index), token_info(&collections, 0).unwrap().2);
+    }
+
+    #[test]
+    fn entropy_pool() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let msg = init_msg();
+        let _res = init(&mut deps, env, msg).unwrap();
+        let mut pools = vec![read_entropy_pool(&deps.storage).load().unwrap()];
 
+        // admin calls advance the pool too
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetPrice { public_price: Uint128(1000), private_price: Uint128(500) }).unwrap();
+        pools.push(read_entropy_pool(&deps.storage).load().unwrap());
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::AddRaffleEntrants { raffle: "launch".to_string(), entrants: vec![HumanAddr::from("minter")] }).unwrap();
+        pools.push(read_entropy_pool(&deps.storage).load().unwrap());
+
+        // the same block and sender still give a new key
+        let env = mock_env("minter", &vec![]);
+        let res = handle(&mut deps, env, HandleMsg::CreateViewingKey { entropy: "dice".to_string(), padding: None }).unwrap();
+        let first: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        pools.push(read_entropy_pool(&deps.storage).load().unwrap());
+        let env = mock_env("minter", &vec![]);
+        let res = handle(&mut deps, env, HandleMsg::CreateViewingKey { entropy: "dice".to_string(), padding: None }).unwrap();
+        let second: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        pools.push(read_entropy_pool(&deps.storage).load().unwrap());
+        assert_ne!(first, second);
+
+        for (i, pool) in pools.iter().enumerate(){
+            assert!(pools[i + 1..].iter().all(|other| other != pool));
+        }
     }
 
-     #[test]
-    fn mint() {
+    #[test]
+    fn raffles() {
         let mut deps = mock_dependencies(20, &[]);
-        let env = mock_env("creator", &coins(1000, "earth"));
+        let env = mock_env("creator", &vec![]);
+        let msg = init_msg();
+        let _res = init(&mut deps, env, msg).unwrap();
+        let entrants: Vec<HumanAddr> = (0..10).map(|i| HumanAddr(format!("entrant{}", i))).collect();
 
-        let msg = InitMsg {
-             white_members: vec![HumanAddr::from("white1"),HumanAddr::from("white2")],
-             admin : HumanAddr::from("admin"),
-             total_supply : Uint128(5),
-             maximum_count :Uint128(1),
-             public_price : Uint128(600000),
-             private_price:Uint128(400000) ,
-             reward_wallet : vec![Wallet{
-                 address:HumanAddr::from("reward1"),
-                 portion:Decimal::from_ratio(70 as u128,100 as u128)
-             },
-             Wallet{
-                 address:HumanAddr::from("reward2"),
-                 portion:Decimal::from_ratio(30 as u128,100 as u128)
-             }
-             ],
-              token_address:HumanAddr::from("token_address"),
-              token_contract_hash :"token_hash".to_string(),
-                check_minted : vec![true,true,true,true,true],
-             human_image:"human".to_string(),
-             human_metadata:"human".to_string(),
-             robot_image:"robot".to_string(),
-             robot_metadata:"robot".to_string(),
-             bull_image:"bull".to_string(),
-             bull_metadata:"bull".to_string()
-            };
-        
-        // we can just call .unwrap() to assert this was a success
-        let res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-        
+        let env = mock_env("entrant0", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::Unauthorized {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::AddRaffleEntrants { raffle: "launch".to_string(), entrants: entrants.clone() }), err);
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::SetNftAddress { nft_address: HumanAddr::from("nft"),nft_contract_hash:"123".to_string() };
-        let _res = handle(&mut deps, env, msg).unwrap();
+        let _res = handle(&mut deps, env, HandleMsg::AddRaffleEntrants { raffle: "launch".to_string(), entrants: entrants.clone() }).unwrap();
+        // entering twice does not double the odds
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::AddRaffleEntrants { raffle: "launch".to_string(), entrants: entrants[..2].to_vec() }).unwrap();
 
-        let state = query_state_info(&deps).unwrap();
-        assert_eq!(state.nft_contract_hash,"123");
+        let env = mock_env("entrant0", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::Unauthorized {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::DrawRaffle { raffle: "launch".to_string(), winners: 3 }), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::NotEnoughEntrants { entrants: 10 }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::DrawRaffle { raffle: "launch".to_string(), winners: 11 }), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::NotEnoughEntrants { entrants: 0 }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::DrawRaffle { raffle: "other".to_string(), winners: 1 }), err);
 
-        let state = query_state_info(&deps).unwrap();
-        assert_eq!(state.nft_address,HumanAddr::from("nft"));
+        let raffle_query = || QueryMsg::GetRaffle { raffle: "launch".to_string() };
+        let result: Option<RaffleResult> = from_binary(&query(&deps, raffle_query()).unwrap()).unwrap();
+        assert_eq!(result, None);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::DrawRaffle { raffle: "launch".to_string(), winners: 3 }).unwrap();
+        let result: Option<RaffleResult> = from_binary(&query(&deps, raffle_query()).unwrap()).unwrap();
+        let result = result.unwrap();
+        assert_eq!(result.entrants, 10);
+        assert_eq!(result.block_height, mock_env("admin", &vec![]).block.height);
+        assert_eq!(result.winners.len(), 3);
+        assert!(result.winners.iter().all(|winner| entrants.contains(winner)));
+        assert!(result.winners[0] != result.winners[1] && result.winners[1] != result.winners[2] && result.winners[0] != result.winners[2]);
+
+        // drawing every entrant picks each of them once
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::AddRaffleEntrants { raffle: "all".to_string(), entrants: entrants.clone() }).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::DrawRaffle { raffle: "all".to_string(), winners: 10 }).unwrap();
+        let mut winners = read_raffle(&deps.storage, "all").unwrap().winners;
+        winners.sort();
+        let mut sorted = entrants.clone();
+        sorted.sort();
+        assert_eq!(winners, sorted);
+
+        // results are final
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::RaffleAlreadyDrawn { name: "launch".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::DrawRaffle { raffle: "launch".to_string(), winners: 3 }), err);
+        let env = mock_env("admin", &vec![]);
+        assert_eq!(handle(&mut deps, env, HandleMsg::AddRaffleEntrants { raffle: "launch".to_string(), entrants: vec![] }), err);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::DrawRaffle { raffle: "launch".to_string(), winners: 3 });
+        let redrawn: Option<RaffleResult> = from_binary(&query(&deps, raffle_query()).unwrap()).unwrap();
+        assert_eq!(redrawn, Some(result));
+    }
 
-        let message = to_binary( 
-             &MetadataMsg{          
-            tokenId:Some("punks1".to_string()),
-            description:Some("secret steam".to_string()),
-            attributes : Some(vec![Trait{
-                trait_type:Some("Clothes".to_string()),
-                value:"value".to_string(),
+    #[test]
+    fn on_chain_metadata() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let msg = init_msg();
+        let _res = init(&mut deps, env, msg).unwrap();
+        let robot = TokenMetadata{
+            name:Some("Robot #0".to_string()),
+            description:Some("The only robot".to_string()),
+            attributes:Some(vec![Trait{
                 display_type:None,
+                trait_type:Some("Eyes".to_string()),
+                value:"Laser".to_string(),
                 max_value:None
             }]),
-            name:Some("name".to_string()),
-            image:Some("image".to_string()),
+            image:None,
+            animation_url:Some("robot.mp4".to_string())
+        };
+        let upload = |collection: &str, start: u32, metadata: Vec<TokenMetadata>| HandleMsg::UploadTokenMetadata { collection: collection.to_string(), start, metadata };
+
+        let env = mock_env("minter", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::Unauthorized {}.into());
+        assert_eq!(handle(&mut deps, env, upload("SteamPunk_Robot", 0, vec![robot.clone()])), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::CollectionNotFound { name: "SteamPunk_Cat".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, upload("SteamPunk_Cat", 0, vec![robot.clone()])), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::IndexOutOfRange { index: 1 }.into());
+        assert_eq!(handle(&mut deps, env, upload("SteamPunk_Robot", 0, vec![robot.clone(), robot.clone()])), err);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, upload("SteamPunk_Robot", 0, vec![robot.clone()])).unwrap();
 
-        }).unwrap();
+        // humans are uploaded in two batches, with no gaps
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::AddMetaData {key:"0".to_string(),metadata: vec![
-            "metadata1".to_string(),
-            "metadata2".to_string(),
-            "metadata3".to_string(),
-            "metadata4".to_string(),
-            "metadata5".to_string(),
-        ] };
-         let _res = handle(&mut deps, env, msg).unwrap();
+        let err: StdResult<HandleResponse> = Err(ContractError::MetadataGap { collection: "SteamPunk_Human".to_string(), uploaded: 0 }.into());
+        assert_eq!(handle(&mut deps, env, upload("SteamPunk_Human", 1, vec![TokenMetadata::default()])), err);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, upload("SteamPunk_Human", 0, vec![TokenMetadata::default()])).unwrap();
+        let progress: Vec<UploadProgress> = from_binary(&query(&deps, QueryMsg::GetUploadProgress {  }).unwrap()).unwrap();
+        assert_eq!(progress.iter().map(|p| p.uploaded).collect::<Vec<u32>>(), vec![1, 0, 1]);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, upload("SteamPunk_Human", 1, vec![TokenMetadata::default()])).unwrap();
+        let progress: Vec<UploadProgress> = from_binary(&query(&deps, QueryMsg::GetUploadProgress {  }).unwrap()).unwrap();
+        assert_eq!(progress[0], UploadProgress{ collection: "SteamPunk_Human".to_string(), uploaded: 2, supply: Uint128(2) });
+
+        let stored: Option<TokenMetadata> = from_binary(&query(&deps, QueryMsg::GetTokenMetadata { collection: "SteamPunk_Robot".to_string(), index: 0 }).unwrap()).unwrap();
+        assert_eq!(stored, Some(robot.clone()));
+
+        // the robot is the last global index
+        let collections = read_collections(&deps.storage).load().unwrap();
+        let (token_id, metadata) = art_metadata(&deps.storage, &collections, 4).unwrap();
+        assert_eq!(token_id, "SteamPunk_Robot.0");
+        let extension = metadata.extension.unwrap();
+        assert_eq!(extension.name, robot.name);
+        assert_eq!(extension.description, robot.description);
+        assert_eq!(extension.attributes, robot.attributes);
+        assert_eq!(extension.animation_url, robot.animation_url);
+        assert_eq!(extension.image, Some("robot/SteamPunk_Robot_0.png".to_string()));
+
+        let (_, metadata) = art_metadata(&deps.storage, &collections, 2).unwrap();
+        assert_eq!(metadata.extension.unwrap().name, None);
+
+        // a removed collection takes its metadata along
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::RemoveCollection { name: "SteamPunk_Robot".to_string() }).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::AddCollection { collection: collections[2].clone() }).unwrap();
+        assert_eq!(read_token_metadata(&deps.storage, "SteamPunk_Robot", 0), None);
+        assert_eq!(read_upload_progress(&deps.storage, "SteamPunk_Robot"), 0);
+        // new uris keep the metadata when the registry is replaced, a new supply drops it
+        let env = mock_env("admin", &vec![]);
+        let mut replaced = collections.clone();
+        replaced[0].image_uri = "human_v2".to_string();
+        let _res = handle(&mut deps, env, HandleMsg::SetCollections { collections: replaced.clone() }).unwrap();
+        assert_eq!(read_upload_progress(&deps.storage, "SteamPunk_Human"), 2);
+        replaced[0].supply = Uint128(3);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetCollections { collections: replaced }).unwrap();
+        assert_eq!(read_upload_progress(&deps.storage, "SteamPunk_Human"), 0);
+        assert_eq!(read_token_metadata(&deps.storage, "SteamPunk_Human", 0), None);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetCollections { collections: collections.clone() }).unwrap();
 
-        let metadata = query_metadata(&deps,"0".to_string()).unwrap();
-   
-        let size:u128 = 0;
-        assert_eq!(metadata[size as usize],"metadata1".to_string());
-        
+        // uploads freeze once minting begins
+        reveal_seed(&mut deps);
         let env = mock_env("admin", &vec![]);
-        let msg = HandleMsg::SetSaleFlag { private_mint: false, public_mint: true };
-        let _res = handle(&mut deps, env, msg).unwrap();
+        let _res = handle(&mut deps, env, HandleMsg::SetSaleFlag { private_mint: false, public_mint: true }).unwrap();
+        let env = mock_env("token_address", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::Receive {
+            sender: HumanAddr::from("minter"),
+            from: HumanAddr::from("minter"),
+            amount: Uint128(1000),
+            msg: to_binary(&MintMsg::default()).unwrap()
+        }).unwrap();
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::MintingStarted {}.into());
+        assert_eq!(handle(&mut deps, env, upload("SteamPunk_Bull", 0, vec![TokenMetadata::default()])), err);
+    }
 
+    #[test]
+    fn private_metadata() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let mut collections = test_collections();
+        collections[2].private_metadata = Some(PrivateMetadata{
+            image_uri:Some("robot_hd".to_string()),
+            description:Some("Unlocked".to_string()),
+            external_url:Some("unlock".to_string()),
+            secret_code:true
+        });
+        let msg = InitMsg {
+            maximum_count : Uint128(5),
+            collections : collections.clone(),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+
+        // the templates are only shown to the admin
+        let public = String::from_utf8(query(&deps, QueryMsg::GetCollections {  }).unwrap().0).unwrap();
+        assert!(!public.contains("robot_hd") && !public.contains("unlock"));
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetViewingKey { key: "admin_key".to_string(), padding: None }).unwrap();
+        let err: StdResult<Binary> = Err(ContractError::InvalidViewingKey {}.into());
+        assert_eq!(query(&deps, QueryMsg::GetPrivateCollections { key: "wrong_key".to_string() }), err);
+        let private: Vec<Collection> = from_binary(&query(&deps, QueryMsg::GetPrivateCollections { key: "admin_key".to_string() }).unwrap()).unwrap();
+        assert_eq!(private, collections);
+
+        let mut rng = Prng::new(b"seed", b"entropy");
+        assert_eq!(unlockable_metadata(&collections, 0, &mut rng).unwrap(), None);
+        let extension = unlockable_metadata(&collections, 4, &mut rng).unwrap().unwrap().extension.unwrap();
+        assert_eq!(extension.image, Some("robot_hd/SteamPunk_Robot_0.png".to_string()));
+        assert_eq!(extension.external_url, Some("unlock".to_string()));
+        assert_eq!(extension.description, Some("Unlocked".to_string()));
+        let code = extension.attributes.unwrap()[0].clone();
+        assert_eq!(code.trait_type, Some("Secret Code".to_string()));
+        assert_eq!(code.value.len(), 16);
+        let other = unlockable_metadata(&collections, 4, &mut rng).unwrap().unwrap().extension.unwrap().attributes.unwrap()[0].clone();
+        assert_ne!(code.value, other.value);
+
+        // minting every token attaches the template to the robot only
+        reveal_seed(&mut deps);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetSaleFlag { private_mint: false, public_mint: true }).unwrap();
         let env = mock_env("token_address", &vec![]);
-         let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
+        let res = handle(&mut deps, env, HandleMsg::Receive {
+            sender: HumanAddr::from("minter"),
+            from: HumanAddr::from("minter"),
+            amount: Uint128(5000),
+            msg: to_binary(&MintMsg{ quantity: Some(5), ..MintMsg::default() }).unwrap()
+        }).unwrap();
+        let batch = match &res.messages[0]{
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => String::from_utf8(msg.0.clone()).unwrap(),
+            _ => panic!("expected a batch mint")
+        };
+        assert_eq!(batch.matches("Secret Code").count(), 1);
+        assert_eq!(batch.matches("robot_hd/SteamPunk_Robot_0.png").count(), 1);
+    }
 
-        
-        let _res = handle(&mut deps, env, msg).unwrap();
-        let user_info = query_user_info(&deps, HumanAddr::from("minter2")).unwrap();
-        let empty : Vec<String> = vec![];
-        assert_eq!(user_info,empty);
-        
-        // let user_info = query_user_info(&deps, HumanAddr::from("white1")).unwrap();
-        // assert_eq!(user_info,vec!["token_id1".to_string()]);       
+    #[test]
+    fn uri_templates() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let mut collections = test_collections();
+        collections[1].token_id_format = Some("{prefix}#{index:04}".to_string());
+        collections[1].metadata_format = Some("{base}/{index:04}.json".to_string());
+        collections[1].image_uri = "ipfs://images".to_string();
+        collections[1].image_format = Some("{base}/{index}.png".to_string());
+        let msg = InitMsg {
+            collections : collections.clone(),
+            ..init_msg()
+        };
+        let _res = init(&mut deps, env, msg).unwrap();
+
+        let preview = |deps: &Extern<_, _, _>, collection: &str, index: u32| query(deps, QueryMsg::PreviewUris { collection: collection.to_string(), index });
+        let bull: UriPreview = from_binary(&preview(&deps, "SteamPunk_Bull", 1).unwrap()).unwrap();
+        assert_eq!(bull, UriPreview{
+            token_id:"SteamPunk_Bull#0001".to_string(),
+            metadata_uri:"bull/0001.json".to_string(),
+            image_uri:"ipfs://images/1.png".to_string()
+        });
+        // collections without formats keep the old layout
+        let human: UriPreview = from_binary(&preview(&deps, "SteamPunk_Human", 1).unwrap()).unwrap();
+        assert_eq!(human.token_id, "SteamPunk_Human.1");
+        assert_eq!(human.metadata_uri, "human/SteamPunk_Human_1.json");
+        assert_eq!(token_info(&collections, 3).unwrap(), (bull.token_id, bull.metadata_uri, bull.image_uri));
+
+        let err: StdResult<Binary> = Err(ContractError::IndexOutOfRange { index: 2 }.into());
+        assert_eq!(preview(&deps, "SteamPunk_Bull", 2), err);
+        let err: StdResult<Binary> = Err(ContractError::CollectionNotFound { name: "SteamPunk_Cat".to_string() }.into());
+        assert_eq!(preview(&deps, "SteamPunk_Cat", 0), err);
+
+        // formats are checked when collections are configured
+        let mut cat = collections[0].clone();
+        cat.name = "SteamPunk_Cat".to_string();
+        cat.token_id_format = Some("{prefix}".to_string());
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::InvalidTemplate { template: "{prefix}".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::AddCollection { collection: cat.clone() }), err);
+        // two collections numbering plain indices would mint the same ids
+        cat.token_id_format = Some("{index:04}".to_string());
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::InvalidTemplate { template: "{index:04}".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::AddCollection { collection: cat.clone() }), err);
+        // "SteamPunk_Cat" + 10 would collide with a "SteamPunk_Cat1" collection's token 0
+        cat.token_id_format = Some("{prefix}{index}".to_string());
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::InvalidTemplate { template: "{prefix}{index}".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::AddCollection { collection: cat.clone() }), err);
+        cat.token_id_format = None;
+        cat.image_format = Some("{base}/{idx}.png".to_string());
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::InvalidTemplate { template: "{base}/{idx}.png".to_string() }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::AddCollection { collection: cat }), err);
+    }
 
-        let env = mock_env("token_address", &vec![]);
-        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
-        let _res = handle(&mut deps, env, msg).unwrap();
+    #[test]
+    fn royalties() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("creator", &vec![]);
+        let msg = init_msg();
+        let _res = init(&mut deps, env, msg).unwrap();
+        let artist = Wallet{
+            address:HumanAddr::from("artist"),
+            portion:Decimal::percent(5)
+        };
+
+        let env = mock_env("artist", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::Unauthorized {}.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::SetRoyalties { royalties: vec![artist.clone()] }), err);
+        let env = mock_env("admin", &vec![]);
+        let err: StdResult<HandleResponse> = Err(ContractError::RoyaltyTooHigh { max_percent: 10 }.into());
+        assert_eq!(handle(&mut deps, env, HandleMsg::SetRoyalties { royalties: vec![artist.clone(), Wallet{ portion: Decimal::percent(6), ..artist.clone() }] }), err);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetRoyalties { royalties: vec![artist.clone()] }).unwrap();
+        let royalties: Vec<Wallet> = from_binary(&query(&deps, QueryMsg::GetRoyalties {  }).unwrap()).unwrap();
+        assert_eq!(royalties, vec![artist]);
 
+        reveal_seed(&mut deps);
+        let env = mock_env("admin", &vec![]);
+        let _res = handle(&mut deps, env, HandleMsg::SetSaleFlag { private_mint: false, public_mint: true }).unwrap();
         let env = mock_env("token_address", &vec![]);
-        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
-        let _res = handle(&mut deps, env, msg).unwrap();
-       
-        let env = mock_env("token_address", &vec![]);
-        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
-        let _res = handle(&mut deps, env, msg).unwrap();
-       
-        let env = mock_env("token_address", &vec![]);
-        let msg = HandleMsg::Receive { sender: HumanAddr::from("white1"), from: HumanAddr::from("xxx"), amount: Uint128(600000), msg: message.clone() };
-        let _res = handle(&mut deps, env, msg).unwrap();
-
-        let state = query_state_info(&deps).unwrap();
-        assert_eq!(state.check_minted,[false,false,false,false,false]);
-        assert_eq!(state.count,Uint128(5))
+        let res = handle(&mut deps, env, HandleMsg::Receive {
+            sender: HumanAddr::from("minter"),
+            from: HumanAddr::from("minter"),
+            amount: Uint128(2000),
+            msg: to_binary(&MintMsg{ quantity: Some(2), ..MintMsg::default() }).unwrap()
+        }).unwrap();
+        let batch = match &res.messages[0]{
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => String::from_utf8(msg.0.clone()).unwrap(),
+            _ => panic!("expected a batch mint")
+        };
+        let royalty = r#""royalty_info":{"decimal_places_in_rates":4,"royalties":[{"recipient":"artist","rate":500}]}"#;
+        assert_eq!(batch.matches(royalty).count(), 2);
     }
-
-    
 }
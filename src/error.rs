@@ -0,0 +1,174 @@
+use cosmwasm_std::{to_vec, HumanAddr, StdError, Uint128};
+use serde::Serialize;
+use snafu::Snafu;
+
+/// Failures of the mint contract. Each one reaches the client as a
+/// `generic_err` whose message is the json of the error, tagged with a stable
+/// snake_case `code`, e.g. `{"code":"wrong_amount","expected":"200","received":"100"}`.
+#[derive(Snafu, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ContractError {
+    #[snafu(display("Unauthorized"))]
+    Unauthorized {},
+
+    #[snafu(display("Sale has not started"))]
+    SaleNotStarted {},
+
+    #[snafu(display("The sale is closed"))]
+    SaleClosed {},
+
+    #[snafu(display("Wrong contract address"))]
+    WrongContractAddress {},
+
+    #[snafu(display("Quantity must be greater than zero"))]
+    InvalidQuantity {},
+
+    #[snafu(display("Can not mint any more, {} tokens left", remaining))]
+    SoldOut { remaining: Uint128 },
+
+    #[snafu(display("You are not whitelisted"))]
+    NotWhitelisted {},
+
+    #[snafu(display("Not exact money, expected {} got {}", expected, received))]
+    WrongAmount { expected: Uint128, received: Uint128 },
+
+    #[snafu(display("Price {} for {} tokens overflows", price, quantity))]
+    PriceOverflow { price: Uint128, quantity: u32 },
+
+    #[snafu(display("You can not mint any more, {} tokens left", remaining))]
+    MintLimitReached { remaining: Uint128 },
+
+    #[snafu(display("Royalties must add up to less than {}%", max_percent))]
+    RoyaltyTooHigh { max_percent: u64 },
+
+    #[snafu(display("Royalty of {} must be positive with at most 4 decimals", address))]
+    InvalidRoyalty { address: HumanAddr },
+
+    #[snafu(display("No rewards to claim"))]
+    NoRewards {},
+
+    #[snafu(display("No dust to sweep"))]
+    NoDust {},
+
+    #[snafu(display("Minting already started"))]
+    MintingStarted {},
+
+    #[snafu(display("The sum must be equal to 1"))]
+    InvalidRewardSplit {},
+
+    #[snafu(display("You can not set both true"))]
+    ConflictingSaleFlags {},
+
+    #[snafu(display("{} name can not be empty", kind))]
+    EmptyName { kind: String },
+
+    #[snafu(display("repeated {} {}", kind, name))]
+    DuplicateName { kind: String, name: String },
+
+    #[snafu(display("{} name {} is reserved", kind, name))]
+    ReservedName { kind: String, name: String },
+
+    #[snafu(display("Collection {} has no supply", name))]
+    ZeroSupply { name: String },
+
+    #[snafu(display("Collection {} not found", name))]
+    CollectionNotFound { name: String },
+
+    #[snafu(display("Token index {} is out of range", index))]
+    IndexOutOfRange { index: u128 },
+
+    #[snafu(display("Collection {} has metadata up to index {}, uploads can not skip ahead", collection, uploaded))]
+    MetadataGap { collection: String, uploaded: u32 },
+
+    #[snafu(display("Invalid uri template {}", template))]
+    InvalidTemplate { template: String },
+
+    #[snafu(display("Tier {} not found", name))]
+    TierNotFound { name: String },
+
+    #[snafu(display("Tier {} was removed, its name can not be reused", name))]
+    RetiredTier { name: String },
+
+    #[snafu(display("Phase {} must end after it starts", name))]
+    InvalidPhaseWindow { name: String },
+
+    #[snafu(display("Phases {} and {} overlap", first, second))]
+    OverlappingPhases { first: String, second: String },
+
+    #[snafu(display("Merkle root must be 32 bytes"))]
+    InvalidMerkleRoot {},
+
+    #[snafu(display("Provenance hash must be 32 bytes"))]
+    InvalidProvenanceHash {},
+
+    #[snafu(display("Seed commitment must be a 32 byte sha256 hash"))]
+    InvalidSeedCommitment {},
+
+    #[snafu(display("Wrong viewing key for this address or viewing key not set"))]
+    InvalidViewingKey {},
+
+    #[snafu(display("The mint seed has not been revealed"))]
+    SeedNotRevealed {},
+
+    #[snafu(display("The mint seed was already revealed"))]
+    SeedAlreadyRevealed {},
+
+    #[snafu(display("The seed does not match the commitment"))]
+    SeedMismatch {},
+
+    #[snafu(display("The sale is not in pre-reveal mode"))]
+    NotPreReveal {},
+
+    #[snafu(display("The art was already revealed"))]
+    AlreadyRevealed {},
+
+    #[snafu(display("The art can only be revealed once the sale is sold out or closed, {} tokens remain", remaining))]
+    SaleNotOver { remaining: Uint128 },
+
+    #[snafu(display("Every minted token is revealed"))]
+    NothingToReveal {},
+
+    #[snafu(display("Raffle {} was already drawn", name))]
+    RaffleAlreadyDrawn { name: String },
+
+    #[snafu(display("Only {} entrants registered", entrants))]
+    NotEnoughEntrants { entrants: u32 },
+
+    #[snafu(display("Permit is not meant for this contract"))]
+    PermitContractMismatch {},
+
+    #[snafu(display("Permit signature verification failed"))]
+    InvalidPermitSignature {},
+
+    #[snafu(display("Permit {} was revoked", permit_name))]
+    PermitRevoked { permit_name: String },
+
+    #[snafu(display("Permit does not grant this query"))]
+    MissingPermission {},
+
+    #[snafu(display("{} is not on the list", address))]
+    UnknownMember { address: HumanAddr },
+}
+
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match to_vec(&err) {
+            Ok(json) => StdError::generic_err(String::from_utf8_lossy(&json)),
+            Err(err) => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        let err: StdError = ContractError::WrongAmount { expected: Uint128(200), received: Uint128(100) }.into();
+        assert_eq!(err, StdError::generic_err(r#"{"code":"wrong_amount","expected":"200","received":"100"}"#));
+
+        let err: StdError = ContractError::Unauthorized {}.into();
+        assert_eq!(err, StdError::generic_err(r#"{"code":"unauthorized"}"#));
+    }
+}
@@ -2,6 +2,12 @@ pub mod contract;
 pub mod msg;
 pub mod state;
 pub mod rand;
+pub mod merkle;
+pub mod error;
+pub mod viewing_key;
+pub mod permit;
+pub mod template;
+pub mod royalty;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm {
@@ -0,0 +1,62 @@
+use cosmwasm_std::Binary;
+
+use crate::rand::sha_256;
+
+/// Hashes a whitelist leaf: the address, followed by `:allocation` when the
+/// tree grants the address a number of tokens.
+pub fn leaf_hash(address: &str, allocation: Option<u32>) -> [u8; 32] {
+    match allocation {
+        Some(allocation) => sha_256(format!("{}:{}", address, allocation).as_bytes()),
+        None => sha_256(address.as_bytes()),
+    }
+}
+
+/// Walks a proof from `leaf` up to `root`. Each pair of nodes is hashed in
+/// sorted order, so the proof does not need to carry left/right flags.
+pub fn verify_proof(root: &[u8], leaf: [u8; 32], proof: &[Binary]) -> bool {
+    let mut hash = leaf;
+    for sibling in proof {
+        let sibling = sibling.as_slice();
+        if sibling.len() != 32 {
+            return false;
+        }
+        let mut data = Vec::with_capacity(64);
+        if hash[..] <= *sibling {
+            data.extend_from_slice(&hash);
+            data.extend_from_slice(sibling);
+        } else {
+            data.extend_from_slice(sibling);
+            data.extend_from_slice(&hash);
+        }
+        hash = sha_256(&data);
+    }
+    hash[..] == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        sha_256(&[low, high].concat())
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let alice = leaf_hash("alice", Some(3));
+        let bob = leaf_hash("bob", None);
+        let carol = leaf_hash("carol", None);
+        let root = node(node(alice, bob), carol);
+
+        let proof = vec![Binary(bob.to_vec()), Binary(carol.to_vec())];
+        assert!(verify_proof(&root, alice, &proof));
+        // the allocation is part of the leaf
+        assert!(!verify_proof(&root, leaf_hash("alice", Some(5)), &proof));
+
+        let proof = vec![Binary(node(alice, bob).to_vec())];
+        assert!(verify_proof(&root, carol, &proof));
+        assert!(!verify_proof(&root, bob, &proof));
+        assert!(!verify_proof(&root, carol, &[Binary(vec![1, 2, 3])]));
+    }
+}
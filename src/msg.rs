@@ -4,24 +4,31 @@ use serde::{Deserialize, Serialize};
 
 use secret_toolkit::snip721::{Trait};
 
+use crate::viewing_key::ViewingKey;
+use crate::permit::Permit;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
    pub white_members : Vec<HumanAddr>,
    pub admin : HumanAddr,
-   pub total_supply:Uint128,
    pub maximum_count:Uint128,
    pub public_price:Uint128,
    pub private_price:Uint128,
    pub reward_wallet:Vec<Wallet>,
    pub token_address:HumanAddr,
    pub token_contract_hash:String,
-   pub check_minted : Vec<bool>,
-   pub human_metadata: String,
-   pub human_image:String,
-   pub robot_metadata:String,
-   pub robot_image:String,
-   pub bull_metadata:String,
-   pub bull_image:String
+   /// false credits reward shares for ClaimRewards instead of sending them
+   /// with each mint; shares are pushed when None
+   pub push_rewards:Option<bool>,
+   /// the total supply is the summed supply of the collections
+   pub collections:Vec<Collection>,
+   /// secret seed of the viewing key generator
+   pub prng_seed:Binary,
+   /// sha256 of the secret seed the admin reveals with RevealSeed before the sale
+   pub seed_commitment:Binary,
+   /// sha256 of the concatenated sha256 hashes of every token image, in
+   /// global index order
+   pub provenance_hash:Binary
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -29,30 +36,110 @@ pub struct InitMsg {
 pub enum HandleMsg {
     
     Receive{sender:HumanAddr,from:HumanAddr,amount:Uint128,msg:Binary},
-    SetTotalSupply{amount: Uint128},
     SetMaximumNft{amount:Uint128},
     SetPrice{public_price:Uint128,private_price:Uint128},
     SetRewardWallet{wallet : Vec<Wallet>},
     ChangeAdmin{address:HumanAddr},
     SetSaleFlag{private_mint:bool,public_mint:bool},
-    SetWhiteUsers{members:Vec<HumanAddr>},
-    AddWhiteUser{member:HumanAddr},
+    /// `tier` selects a tier's member list instead of the plain whitelist
+    AddWhiteUsers{members:Vec<HumanAddr>,tier:Option<String>},
+    RemoveWhiteUsers{members:Vec<HumanAddr>,tier:Option<String>},
     SetNftAddress{nft_address:HumanAddr,nft_contract_hash:String},
     SetTokenAddres{token_address:HumanAddr,token_contract_hash:String},
-    AddMetaData {key:String,metadata: Vec<String>},
-    SetMetaData {metadata: Vec<String>},
-    SetRandom{},
+    /// sets the metadata of tokens `start..start + metadata.len()` of a
+    /// collection, continuing or overwriting earlier batches, until minting starts
+    UploadTokenMetadata{collection:String,start:u32,metadata:Vec<TokenMetadata>},
+    /// registers giveaway entrants, until the raffle is drawn
+    AddRaffleEntrants{raffle:String,entrants:Vec<HumanAddr>},
+    /// draws `winners` distinct entrants
+    DrawRaffle{raffle:String,winners:u32},
+    SetCollections{collections:Vec<Collection>},
+    AddCollection{collection:Collection},
+    RemoveCollection{name:String},
+    SetPhases{phases:Vec<Phase>},
+    SetMerkleRoot{root:Option<Binary>},
+    /// tiers left out are removed for good, their names can not be set again
+    SetTiers{tiers:Vec<Tier>},
+    SetWalletLimits{public_maximum_count:Option<Uint128>,wallet_maximum_count:Option<Uint128>},
+    SetRewardMode{push:bool},
+    ClaimRewards{},
+    SetDustWallet{address:Option<HumanAddr>},
+    /// sends the tracked dust to `recipient`, or to the admin
+    SweepDust{recipient:Option<HumanAddr>},
+    /// secondary sale royalties attached to every token minted from now on
+    SetRoyalties{royalties:Vec<Wallet>},
+    CreateViewingKey{entropy:String,padding:Option<String>},
+    SetViewingKey{key:String,padding:Option<String>},
+    RevokePermit{permit_name:String,padding:Option<String>},
+    /// Stores the seed behind seed_commitment. It has to come before the sale
+    /// opens, random mints fail until then; GetProvenance publishes it once
+    /// the sale is sold out or closed.
+    RevealSeed{seed:Binary},
+    /// mint with this placeholder image until Reveal; None mints revealed tokens
+    SetPlaceholder{uri:Option<String>},
+    /// ends the sale for good; unsold tokens are never minted, so the minted
+    /// ones can be revealed
+    CloseSale{},
+    /// shifts the pre-reveal tokens onto the art once the sale is sold out or closed
+    Reveal{seed:Binary},
+    /// sends the next batch of metadata updates of a reveal
+    ContinueReveal{},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetCount returns the current count as a json-encoded number
-    GetStateInfo {},
-    GetWhiteUsers{},
-    GetUserInfo{address:HumanAddr},
-    GetMetadata{key:String},
-    GetRand{}
+    // queries do not see the block, so the caller passes the current time
+    GetSaleInfo{time:u64},
+    /// full configuration, for the admin's viewing key only
+    GetConfig{key:String},
+    /// pages the list by position; removing a member moves the last member
+    /// into its place, so pages read across a removal can skip or repeat it
+    GetWhiteUsers{start_after:Option<HumanAddr>,limit:Option<u32>,tier:Option<String>},
+    IsWhitelisted{address:HumanAddr,tier:Option<String>},
+    GetUserInfo{address:HumanAddr,key:String},
+    GetTokenMetadata{collection:String,index:u32},
+    GetUploadProgress{},
+    /// renders the collection's formats for an index inside the collection
+    PreviewUris{collection:String,index:u32},
+    GetRaffle{raffle:String},
+    /// collections without their private metadata
+    GetCollections{},
+    /// collections with their private metadata, for the admin's viewing key only
+    GetPrivateCollections{key:String},
+    /// reveals which slots are left, for the admin's viewing key only
+    GetMintedStatus{start:u32,limit:u32,key:String},
+    // queries do not see the block, so the caller passes the current time
+    GetActivePhase{time:u64},
+    GetTiers{},
+    GetPendingRewards{address:HumanAddr,key:String},
+    GetDust{},
+    GetRoyalties{},
+    GetRevealInfo{},
+    GetProvenance{},
+    /// user-private queries, authenticated by a signed permit instead of a viewing key
+    WithPermit{permit:Permit,query:QueryWithPermit},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    UserInfo{},
+    PendingRewards{},
+    /// admin only
+    Config{},
+    /// admin only
+    MintedStatus{start:u32,limit:u32},
+    /// admin only
+    PrivateCollections{},
+}
+
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    CreateViewingKey{key:ViewingKey},
 }
 
 
@@ -64,18 +151,193 @@ pub struct Wallet {
 }
 
 
+/// One family of tokens sharing a name prefix and a pair of base uris.
+/// Collections are laid out back to back, so the global token index is
+/// split into a collection and an index local to that collection.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Collection {
+    /// prefix of the token ids and file names, e.g. "SteamPunk_Human"
+    pub name: String,
+    /// base uri of the metadata json files
+    pub metadata_uri: String,
+    /// base uri of the images
+    pub image_uri: String,
+    /// number of tokens in the collection
+    pub supply: Uint128,
+    /// private metadata of every token of the collection, only shown to its owner
+    #[serde(default)]
+    pub private_metadata: Option<PrivateMetadata>,
+    /// Formats of the token id and of the metadata and image urls, built from
+    /// `{prefix}` (the name), `{base}` (the uri), `{index}` and zero padded
+    /// `{index:0N}`. Unset formats keep the "{prefix}.{index}" ids and
+    /// "{base}/{prefix}_{index}.json" / ".png" files. Token ids need both
+    /// `{prefix}` and an index, apart from each other, so they stay unique
+    /// across collections.
+    #[serde(default)]
+    pub token_id_format: Option<String>,
+    #[serde(default)]
+    pub metadata_format: Option<String>,
+    #[serde(default)]
+    pub image_format: Option<String>
+}
+
+/// Public view of a collection, the private metadata is left out
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub metadata_uri: String,
+    pub image_uri: String,
+    pub supply: Uint128,
+    pub token_id_format: Option<String>,
+    pub metadata_format: Option<String>,
+    pub image_format: Option<String>
+}
+
+/// Template of the SNIP-721 private metadata attached to a collection's tokens
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct PrivateMetadata {
+    /// base uri of the high resolution images, named by the collection's image_format
+    pub image_uri: Option<String>,
+    pub description: Option<String>,
+    /// link to the unlockable content
+    pub external_url: Option<String>,
+    /// adds a random "Secret Code" trait, different for every token
+    #[serde(default)]
+    pub secret_code: bool
+}
+
+
+/// A scheduled sale phase, active while `start <= env.block.time < end`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Phase {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+    /// price of one token
+    pub price: Uint128,
+    /// maximum number of tokens one wallet can mint during the phase
+    pub per_wallet_limit: Option<Uint128>,
+    pub whitelist_only: bool,
+    /// tiers whose members may mint at the tier price during the phase
+    #[serde(default)]
+    pub tiers: Vec<String>,
+    /// only members of `tiers` may mint, e.g. an early phase for og holders
+    #[serde(default)]
+    pub tiers_only: bool
+}
+
+
+/// A named group of wallets with its own price and caps, e.g. "og"
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Tier {
+    pub name: String,
+    /// price of one token
+    pub price: Uint128,
+    /// maximum number of tokens one wallet can mint at this tier
+    pub per_wallet_cap: Uint128,
+    /// maximum number of tokens minted at this tier across all wallets
+    pub allocation: Uint128
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TierInfo {
+    pub tier: Tier,
+    /// number of tokens minted at this tier so far
+    pub minted: Uint128
+}
+
+
+/// Public view of the sale
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SaleInfoResponse {
+    pub public_price: Uint128,
+    pub private_price: Uint128,
+    pub public_mint: bool,
+    pub private_mint: bool,
+    /// phase active at the queried time
+    pub phase: Option<Phase>,
+    pub minted: Uint128,
+    pub total_supply: Uint128,
+    /// no more tokens can be minted
+    pub closed: bool
+}
+
+
+/// Token `id` shows the image at global index `(id + starting_index) % total_supply`
+/// of the sequence the provenance hash commits to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProvenanceResponse {
+    pub provenance_hash: Binary,
+    /// reveal offset, None until the art is revealed
+    pub starting_index: Option<u32>,
+    pub total_supply: Uint128,
+    /// sha256 of the mint seed, committed to at init
+    pub seed_commitment: Binary,
+    /// the mint seed, None while tokens can still be minted
+    pub mint_seed: Option<Binary>
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UserInfoResponse {
+    pub token_ids: Vec<String>,
+    pub minted: Uint128,
+    /// tokens the wallet can still mint across all phases, None when uncapped
+    pub remaining: Option<Uint128>
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MintedStatusResponse {
+    pub start: u32,
+    /// minted flag of every token index from `start` on
+    pub minted: Vec<bool>
+}
+
+
+/// Payload of the snip20 Receive message sent along with the payment
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug, Default)]
+pub struct MintMsg {
+    /// number of tokens to mint, defaults to one
+    pub quantity: Option<u32>,
+    /// merkle proof of the sender's whitelist leaf
+    pub proof: Option<Vec<Binary>>,
+    /// allocation encoded in the sender's whitelist leaf, if any; it caps the wallet over the whole sale
+    pub allocation: Option<u32>,
+    /// mixed into the draw along with the revealed seed
+    pub entropy: Option<String>,
+}
+
+
+/// On-chain metadata of one token of a collection
 #[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug, Default)]
-pub struct MetadataMsg {
-    pub tokenId:Option<String>,
+pub struct TokenMetadata {
      /// name of the item
     pub name: Option<String>,
     /// item description
     pub description: Option<String>,
     /// item attributes
     pub attributes: Option<Vec<Trait>>,
-   
-    /// url to the image
-    pub image: Option<String>,  
-  
+    /// url to the image, instead of the one built from the collection's image_uri
+    pub image: Option<String>,
+    pub animation_url: Option<String>,
+}
+
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
+pub struct UriPreview {
+    pub token_id: String,
+    pub metadata_uri: String,
+    pub image_uri: String
+}
+
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
+pub struct UploadProgress {
+    pub collection: String,
+    /// tokens `0..uploaded` of the collection have metadata
+    pub uploaded: u32,
+    pub supply: Uint128
 }
 
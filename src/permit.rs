@@ -0,0 +1,167 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_vec, Api, Binary, CanonicalAddr, HumanAddr, StdResult, Uint128};
+use ripemd160::{Digest, Ripemd160};
+
+use crate::error::ContractError;
+use crate::rand::sha_256;
+
+/// SNIP-24 query permit: the params are signed offline by the wallet, and
+/// the signer's address is recovered from the public key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitParams {
+    pub allowed_tokens: Vec<HumanAddr>,
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PubKey {
+    /// always "tendermint/PubKeySecp256k1"
+    pub r#type: String,
+    pub value: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// mint history
+    History,
+    /// pending rewards
+    Balance,
+    /// everything the owner can see
+    Owner,
+}
+
+impl Permit {
+    pub fn check_permission(&self, permission: Permission) -> bool {
+        self.params.permissions.iter().any(|p| *p == permission || *p == Permission::Owner)
+    }
+}
+
+// The amino sign doc the wallet signs. Fields are declared in alphabetical
+// order because the signed bytes are the json with sorted keys.
+#[derive(Serialize)]
+struct SignedPermit<'a> {
+    account_number: Uint128,
+    chain_id: &'a str,
+    fee: Fee,
+    memo: &'a str,
+    msgs: [PermitMsg<'a>; 1],
+    sequence: Uint128,
+}
+
+#[derive(Serialize)]
+struct Fee {
+    amount: [Coin; 1],
+    gas: Uint128,
+}
+
+#[derive(Serialize)]
+struct Coin {
+    amount: Uint128,
+    denom: &'static str,
+}
+
+#[derive(Serialize)]
+struct PermitMsg<'a> {
+    r#type: &'static str,
+    value: PermitContent<'a>,
+}
+
+#[derive(Serialize)]
+struct PermitContent<'a> {
+    allowed_tokens: &'a [HumanAddr],
+    permissions: &'a [Permission],
+    permit_name: &'a str,
+}
+
+/// Bytes a wallet signs for `params`.
+pub fn permit_sign_bytes(params: &PermitParams) -> StdResult<Vec<u8>> {
+    to_vec(&SignedPermit {
+        account_number: Uint128(0),
+        chain_id: &params.chain_id,
+        fee: Fee {
+            amount: [Coin { amount: Uint128(0), denom: "uscrt" }],
+            gas: Uint128(1),
+        },
+        memo: "",
+        msgs: [PermitMsg {
+            r#type: "query_permit",
+            value: PermitContent {
+                allowed_tokens: &params.allowed_tokens,
+                permissions: &params.permissions,
+                permit_name: &params.permit_name,
+            },
+        }],
+        sequence: Uint128(0),
+    })
+}
+
+/// Account address of a secp256k1 public key: ripemd160(sha256(pubkey)).
+pub fn pubkey_to_canonical(pubkey: &[u8]) -> CanonicalAddr {
+    let mut hasher = Ripemd160::new();
+    hasher.update(sha_256(pubkey));
+    CanonicalAddr(Binary(hasher.finalize().to_vec()))
+}
+
+/// Checks that the permit is meant for this contract and carries a valid
+/// signature, and returns the address of the signer. Revocation is checked
+/// by the caller, which owns the storage.
+pub fn validate<A: Api>(api: &A, permit: &Permit, contract_address: &HumanAddr) -> StdResult<HumanAddr> {
+    if !permit.params.allowed_tokens.contains(contract_address) {
+        return Err(ContractError::PermitContractMismatch {}.into());
+    }
+
+    let message_hash = sha_256(&permit_sign_bytes(&permit.params)?);
+    let pubkey = permit.signature.pub_key.value.as_slice();
+    let verified = api
+        .secp256k1_verify(&message_hash, permit.signature.signature.as_slice(), pubkey)
+        .unwrap_or(false);
+    if !verified {
+        return Err(ContractError::InvalidPermitSignature {}.into());
+    }
+
+    api.human_address(&pubkey_to_canonical(pubkey))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permit_sign_bytes() {
+        let params = PermitParams {
+            allowed_tokens: vec![HumanAddr::from("secret1contract")],
+            permit_name: "mint history".to_string(),
+            chain_id: "secret-4".to_string(),
+            permissions: vec![Permission::History],
+        };
+        let bytes = permit_sign_bytes(&params).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            concat!(
+                r#"{"account_number":"0","chain_id":"secret-4","fee":{"amount":[{"amount":"0","denom":"uscrt"}],"gas":"1"},"#,
+                r#""memo":"","msgs":[{"type":"query_permit","value":{"allowed_tokens":["secret1contract"],"#,
+                r#""permissions":["history"],"permit_name":"mint history"}}],"sequence":"0"}"#
+            )
+        );
+
+        // 33 byte compressed key to 20 byte account address
+        assert_eq!(pubkey_to_canonical(&[2u8; 33]).as_slice().len(), 20);
+    }
+}
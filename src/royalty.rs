@@ -0,0 +1,142 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CosmosMsg, Decimal, HumanAddr, StdResult, Uint128};
+use secret_toolkit::snip721::Metadata;
+use secret_toolkit::utils::HandleCallback;
+
+use crate::error::ContractError;
+use crate::msg::Wallet;
+
+/// rates are sent to the SNIP-721 contract in hundredths of a percent
+pub const DECIMAL_PLACES_IN_RATES: u8 = 4;
+/// cap the configured royalties must stay below
+pub const MAX_ROYALTY_PERCENT: u64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Royalty {
+    pub recipient: HumanAddr,
+    /// share of a sale, with `decimal_places_in_rates` decimals
+    pub rate: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoyaltyInfo {
+    pub decimal_places_in_rates: u8,
+    pub royalties: Vec<Royalty>,
+}
+
+/// SNIP-721 `Mint` with the royalty field secret-toolkit's version lacks.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Mint {
+    pub token_id: Option<String>,
+    pub owner: Option<HumanAddr>,
+    pub public_metadata: Option<Metadata>,
+    pub private_metadata: Option<Metadata>,
+    pub royalty_info: Option<RoyaltyInfo>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum HandleMsg {
+    BatchMintNft { mints: Vec<Mint>, padding: Option<String> },
+}
+
+impl HandleCallback for HandleMsg {
+    const BLOCK_SIZE: usize = 256;
+}
+
+pub fn batch_mint_nft_msg(
+    mints: Vec<Mint>,
+    padding: Option<String>,
+    callback_code_hash: String,
+    contract_addr: HumanAddr,
+) -> StdResult<CosmosMsg> {
+    HandleMsg::BatchMintNft { mints, padding }.to_cosmos_msg(callback_code_hash, contract_addr, None)
+}
+
+/// Checks that the royalties add up to less than MAX_ROYALTY_PERCENT and that
+/// every share can be expressed in DECIMAL_PLACES_IN_RATES decimals.
+pub fn validate_royalties(royalties: &[Wallet]) -> StdResult<()> {
+    let mut total = Decimal::zero();
+    for royalty in royalties {
+        let rate = rate(royalty.portion);
+        if royalty.portion == Decimal::zero() || Decimal::from_ratio(rate, rate_scale()) != royalty.portion {
+            return Err(ContractError::InvalidRoyalty { address: royalty.address.clone() }.into());
+        }
+        total = total + royalty.portion;
+    }
+    if total >= Decimal::percent(MAX_ROYALTY_PERCENT) {
+        return Err(ContractError::RoyaltyTooHigh { max_percent: MAX_ROYALTY_PERCENT }.into());
+    }
+    Ok(())
+}
+
+/// Royalty info attached to every mint, None when no royalties are set.
+pub fn royalty_info(royalties: &[Wallet]) -> Option<RoyaltyInfo> {
+    if royalties.is_empty() {
+        return None;
+    }
+    Some(RoyaltyInfo {
+        decimal_places_in_rates: DECIMAL_PLACES_IN_RATES,
+        royalties: royalties
+            .iter()
+            .map(|royalty| Royalty { recipient: royalty.address.clone(), rate: rate(royalty.portion) as u16 })
+            .collect(),
+    })
+}
+
+fn rate_scale() -> u128 {
+    10u128.pow(DECIMAL_PLACES_IN_RATES as u32)
+}
+
+fn rate(portion: Decimal) -> u128 {
+    (Uint128(rate_scale()) * portion).u128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::WasmMsg;
+
+    #[test]
+    fn test_royalties() {
+        let artist = Wallet { address: HumanAddr::from("artist"), portion: Decimal::permille(25) };
+        let studio = Wallet { address: HumanAddr::from("studio"), portion: Decimal::percent(5) };
+        assert!(validate_royalties(&[]).is_ok());
+        assert!(validate_royalties(&[artist.clone(), studio.clone()]).is_ok());
+        assert!(validate_royalties(&[artist.clone(), Wallet { portion: Decimal::percent(8), ..studio.clone() }]).is_err());
+        assert!(validate_royalties(&[Wallet { portion: Decimal::percent(MAX_ROYALTY_PERCENT), ..studio.clone() }]).is_err());
+        assert!(validate_royalties(&[Wallet { portion: Decimal::permille(MAX_ROYALTY_PERCENT * 10 - 1), ..studio.clone() }]).is_ok());
+        assert!(validate_royalties(&[Wallet { portion: Decimal::from_ratio(1u128, 3u128), ..artist.clone() }]).is_err());
+        assert!(validate_royalties(&[Wallet { portion: Decimal::zero(), ..artist.clone() }]).is_err());
+
+        assert_eq!(royalty_info(&[]), None);
+        let info = royalty_info(&[artist, studio]).unwrap();
+        let msg = batch_mint_nft_msg(
+            vec![Mint {
+                token_id: Some("SteamPunk_Human.0".to_string()),
+                owner: Some(HumanAddr::from("minter")),
+                public_metadata: None,
+                private_metadata: None,
+                royalty_info: Some(info),
+                memo: None,
+            }],
+            None,
+            "nft_hash".to_string(),
+            HumanAddr::from("nft"),
+        )
+        .unwrap();
+        match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                let json = String::from_utf8(msg.0).unwrap();
+                assert!(json.contains(concat!(
+                    r#""royalty_info":{"decimal_places_in_rates":4,"#,
+                    r#""royalties":[{"recipient":"artist","rate":250},{"recipient":"studio","rate":500}]}"#
+                )));
+            }
+            _ => panic!("expected an execute message"),
+        }
+    }
+}
@@ -1,20 +1,63 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::msg::{Wallet, MetadataMsg};
+use crate::msg::{Wallet, Collection, Phase, Tier, TokenMetadata};
 
-use cosmwasm_std::{Storage, Uint128, HumanAddr,StdResult};
+use cosmwasm_std::{Storage, Uint128, HumanAddr,StdResult, Binary};
 use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton,bucket,bucket_read};
 // use cw_storage_plus::Map;
 
 pub static CONFIG_KEY: &[u8] = b"config";
-pub static CONFIG_RANDOM: &[u8] = b"config_random";
-pub static CONFIG_METADATA : &[u8] = b"config_metadata";
-pub static CONFIG_MEMBERS: &[u8] = b"config_members";
+pub static CONFIG_COLLECTIONS: &[u8] = b"config_collections";
+pub static CONFIG_PHASES: &[u8] = b"config_phases";
+pub static CONFIG_TIERS: &[u8] = b"config_tiers";
+pub static CONFIG_DUST: &[u8] = b"config_dust";
+pub static CONFIG_PRNG_SEED: &[u8] = b"config_prng_seed";
+pub static CONFIG_MINT_SEED: &[u8] = b"config_mint_seed";
+pub static CONFIG_REVEAL: &[u8] = b"config_reveal";
+pub static CONFIG_ENTROPY_POOL: &[u8] = b"config_entropy_pool";
 pub const CONFIG_USERS: &[u8] = b"User";
-pub const CONFIG_URL: &[u8] = b"Url";
+pub const CONFIG_MEMBERS: &[u8] = b"Members";
+pub const CONFIG_MEMBER_LIST: &[u8] = b"MemberList";
+pub const CONFIG_MEMBER_COUNT: &[u8] = b"MemberCount";
+pub const CONFIG_POOL: &[u8] = b"Pool";
+pub const CONFIG_MINTED: &[u8] = b"Minted";
+pub const CONFIG_PHASE_MINTS: &[u8] = b"PhaseMints";
+pub const CONFIG_TIER_MINTS: &[u8] = b"TierMints";
+pub const CONFIG_ALLOCATION_MINTS: &[u8] = b"AllocationMints";
+pub const CONFIG_TIER_TOTALS: &[u8] = b"TierTotals";
+pub const CONFIG_RETIRED_TIERS: &[u8] = b"RetiredTiers";
+pub const CONFIG_PENDING_REWARDS: &[u8] = b"PendingRewards";
+pub const CONFIG_RAFFLES: &[u8] = b"Raffles";
+pub const CONFIG_TOKEN_METADATA: &[u8] = b"TokenMetadata";
+pub const CONFIG_UPLOAD_PROGRESS: &[u8] = b"UploadProgress";
+pub const CONFIG_VIEWING_KEYS: &[u8] = b"ViewingKeys";
+pub const CONFIG_REVOKED_PERMITS: &[u8] = b"RevokedPermits";
+
+/// number of token slots tracked by one page of the minted bitset
+pub const MINTED_PAGE_BITS: u32 = 256;
 
 // pub const USERS: Map<&str, Vec<String>> = Map::new("User");
 
+/// Outcome of a drawn giveaway.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RaffleResult {
+    pub winners: Vec<HumanAddr>,
+    /// size of the entrant set the winners were drawn from
+    pub entrants: u32,
+    pub block_height: u64,
+}
+
+/// Progress of a delayed reveal. Token `id` shows the art of global index
+/// `(id + offset) % total_supply`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RevealInfo {
+    pub offset: u32,
+    /// tokens below this id already got their metadata updated
+    pub updated: u32,
+    /// tokens minted before the reveal, which need an update
+    pub target: u32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub count: Uint128,
@@ -30,13 +73,27 @@ pub struct State {
     pub nft_contract_hash:String,
     pub token_address:HumanAddr,
     pub token_contract_hash:String,
-    pub check_minted : Vec<bool>,
-    pub human_metadata: String,
-    pub human_image:String,
-    pub robot_metadata:String,
-    pub robot_image:String,
-    pub bull_metadata:String,
-    pub bull_image:String
+    pub merkle_root:Option<Binary>,
+    /// per wallet cap of the public sale, unlimited when None
+    pub public_maximum_count:Option<Uint128>,
+    /// per wallet cap across every phase, unlimited when None
+    pub wallet_maximum_count:Option<Uint128>,
+    /// send each reward share with the mint instead of crediting it for ClaimRewards
+    pub push_rewards:bool,
+    /// receives the rounding remainder of every reward split; kept as dust when None
+    pub dust_wallet:Option<HumanAddr>,
+    /// own address, which permits must list; queries do not get an Env
+    pub contract_address:HumanAddr,
+    /// sha256 of the mint seed, fixed at init
+    pub seed_commitment:Binary,
+    /// set at init and never changed
+    pub provenance_hash:Binary,
+    /// image of unrevealed tokens; tokens are minted in order while this is set
+    pub placeholder_uri:Option<String>,
+    /// set by CloseSale, nothing can be minted afterwards
+    pub sale_closed:bool,
+    /// secondary sale royalties of minted tokens
+    pub royalties:Vec<Wallet>
 }
 
 pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, State> {
@@ -47,29 +104,157 @@ pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, State> {
     singleton_read(storage, CONFIG_KEY)
 }
 
-pub fn save_rand<S: Storage>(storage: &mut S) -> Singleton<S, u16> {
-    singleton(storage, CONFIG_RANDOM)
+
+
+/// Whitelist members are keyed by address and also listed by position, so the
+/// list can be paged without iterating storage. Removing a member moves the
+/// last member into the freed position. Each tier keeps its own list, named
+/// after the tier; the plain whitelist is the list with an empty name.
+pub fn read_member_count<S: Storage>(storage: &S, list: &str) -> u32 {
+    match bucket_read(CONFIG_MEMBER_COUNT, storage).load(list.as_bytes()) {
+        Ok(v) => v,
+        _ => 0,
+    }
+}
+
+pub fn read_member_position<S: Storage>(storage: &S, list: &str, member: &HumanAddr) -> Option<u32> {
+    match bucket_read(CONFIG_MEMBERS, storage).load(&scoped_key(list, member.as_str().as_bytes())) {
+        Ok(v) => Some(v),
+        _ => None,
+    }
+}
+
+pub fn read_member_at<S: Storage>(storage: &S, list: &str, position: u32) -> Option<HumanAddr> {
+    match bucket_read(CONFIG_MEMBER_LIST, storage).load(&scoped_key(list, &position.to_be_bytes())) {
+        Ok(v) => Some(v),
+        _ => None,
+    }
+}
+
+pub fn is_member<S: Storage>(storage: &S, list: &str, member: &HumanAddr) -> bool {
+    read_member_position(storage, list, member).is_some()
+}
+
+/// Returns false when the address was already on the list.
+pub fn add_member<S: Storage>(storage: &mut S, list: &str, member: &HumanAddr) -> StdResult<bool> {
+    if is_member(storage, list, member) {
+        return Ok(false);
+    }
+    let count = read_member_count(storage, list);
+    bucket(CONFIG_MEMBERS, storage).save(&scoped_key(list, member.as_str().as_bytes()), &count)?;
+    bucket(CONFIG_MEMBER_LIST, storage).save(&scoped_key(list, &count.to_be_bytes()), member)?;
+    bucket(CONFIG_MEMBER_COUNT, storage).save(list.as_bytes(), &(count + 1))?;
+    Ok(true)
+}
+
+/// Returns false when the address was not on the list.
+pub fn remove_member<S: Storage>(storage: &mut S, list: &str, member: &HumanAddr) -> StdResult<bool> {
+    let position = match read_member_position(storage, list, member) {
+        Some(position) => position,
+        None => return Ok(false),
+    };
+    let last = read_member_count(storage, list) - 1;
+    if position != last {
+        if let Some(last_member) = read_member_at(storage, list, last) {
+            bucket(CONFIG_MEMBERS, storage).save(&scoped_key(list, last_member.as_str().as_bytes()), &position)?;
+            bucket(CONFIG_MEMBER_LIST, storage).save(&scoped_key(list, &position.to_be_bytes()), &last_member)?;
+        }
+    }
+    bucket::<S, u32>(CONFIG_MEMBERS, storage).remove(&scoped_key(list, member.as_str().as_bytes()));
+    bucket::<S, HumanAddr>(CONFIG_MEMBER_LIST, storage).remove(&scoped_key(list, &last.to_be_bytes()));
+    bucket(CONFIG_MEMBER_COUNT, storage).save(list.as_bytes(), &last)?;
+    Ok(true)
+}
+
+pub fn store_collections<S: Storage>(storage: &mut S) -> Singleton<S, Vec<Collection>> {
+    singleton(storage, CONFIG_COLLECTIONS)
 }
 
-pub fn read_rand<S: Storage>(storage: &S) -> ReadonlySingleton<S, u16> {
-    singleton_read(storage, CONFIG_RANDOM)
+pub fn read_collections<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<Collection>> {
+    singleton_read(storage, CONFIG_COLLECTIONS)
 }
 
-pub fn save_metadata<S: Storage>(storage: &mut S) -> Singleton<S, Vec<String>> {
-    singleton(storage, CONFIG_METADATA)
+pub fn store_phases<S: Storage>(storage: &mut S) -> Singleton<S, Vec<Phase>> {
+    singleton(storage, CONFIG_PHASES)
 }
 
-pub fn read_metadata<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<String>> {
-    singleton_read(storage, CONFIG_METADATA)
+pub fn read_phases<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<Phase>> {
+    singleton_read(storage, CONFIG_PHASES)
 }
 
+pub fn store_tiers<S: Storage>(storage: &mut S) -> Singleton<S, Vec<Tier>> {
+    singleton(storage, CONFIG_TIERS)
+}
+
+pub fn read_tiers<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<Tier>> {
+    singleton_read(storage, CONFIG_TIERS)
+}
+
+/// length-prefixes `scope` so that e.g. phase "a" + user "bc" and phase "ab"
+/// + user "c" never share a key
+fn scoped_key(scope: &str, key: &[u8]) -> Vec<u8> {
+    let mut scoped = (scope.len() as u16).to_be_bytes().to_vec();
+    scoped.extend_from_slice(scope.as_bytes());
+    scoped.extend_from_slice(key);
+    scoped
+}
+
+/// number of tokens a wallet minted during a phase
+pub fn store_phase_mints<S: Storage>(storage: &mut S, phase: &str, user: &str, count: u32) -> StdResult<()> {
+    bucket(CONFIG_PHASE_MINTS, storage).save(&scoped_key(phase, user.as_bytes()), &count)
+}
+
+pub fn read_phase_mints<S: Storage>(storage: &S, phase: &str, user: &str) -> u32 {
+    match bucket_read(CONFIG_PHASE_MINTS, storage).load(&scoped_key(phase, user.as_bytes())) {
+        Ok(v) => v,
+        _ => 0,
+    }
+}
+
+/// number of tokens a wallet minted against its merkle allocation, across all phases
+pub fn store_allocation_mints<S: Storage>(storage: &mut S, user: &str, count: u32) -> StdResult<()> {
+    bucket(CONFIG_ALLOCATION_MINTS, storage).save(user.as_bytes(), &count)
+}
+
+pub fn read_allocation_mints<S: Storage>(storage: &S, user: &str) -> u32 {
+    match bucket_read(CONFIG_ALLOCATION_MINTS, storage).load(user.as_bytes()) {
+        Ok(v) => v,
+        _ => 0,
+    }
+}
 
-pub fn store_members<S: Storage>(storage: &mut S) -> Singleton<S, Vec<HumanAddr>> {
-    singleton(storage, CONFIG_MEMBERS)
+/// number of tokens a wallet minted at a tier's price
+pub fn store_tier_mints<S: Storage>(storage: &mut S, tier: &str, user: &str, count: u32) -> StdResult<()> {
+    bucket(CONFIG_TIER_MINTS, storage).save(&scoped_key(tier, user.as_bytes()), &count)
 }
 
-pub fn read_members<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<HumanAddr>> {
-    singleton_read(storage, CONFIG_MEMBERS)
+pub fn read_tier_mints<S: Storage>(storage: &S, tier: &str, user: &str) -> u32 {
+    match bucket_read(CONFIG_TIER_MINTS, storage).load(&scoped_key(tier, user.as_bytes())) {
+        Ok(v) => v,
+        _ => 0,
+    }
+}
+
+/// number of tokens minted at a tier's price across all wallets
+pub fn store_tier_total<S: Storage>(storage: &mut S, tier: &str, count: u32) -> StdResult<()> {
+    bucket(CONFIG_TIER_TOTALS, storage).save(tier.as_bytes(), &count)
+}
+
+pub fn read_tier_total<S: Storage>(storage: &S, tier: &str) -> u32 {
+    match bucket_read(CONFIG_TIER_TOTALS, storage).load(tier.as_bytes()) {
+        Ok(v) => v,
+        _ => 0,
+    }
+}
+
+/// Names of removed tiers. Their member lists and mint counters can not be
+/// enumerated for clearing, so the names are never handed out again.
+pub fn retire_tier<S: Storage>(storage: &mut S, tier: &str) -> StdResult<()> {
+    bucket(CONFIG_RETIRED_TIERS, storage).save(tier.as_bytes(), &true)
+}
+
+pub fn is_retired_tier<S: Storage>(storage: &S, tier: &str) -> bool {
+    bucket_read::<S, bool>(CONFIG_RETIRED_TIERS, storage).load(tier.as_bytes()).is_ok()
 }
 
 pub fn store_user_info<S: Storage>(storage: &mut S, user: &str, user_info: Vec<String>) -> StdResult<()> {
@@ -83,14 +268,166 @@ pub fn read_user_info<S: Storage>(storage: &S, user: &str) -> Option<Vec<String>
     }
 }
 
-pub fn store_url_info<S: Storage>(storage: &mut S, user: &str, user_info: Vec<String>) -> StdResult<()> {
-    bucket(CONFIG_URL, storage).save(user.as_bytes(), &user_info)
+/// reward tokens a beneficiary has earned but not claimed yet
+pub fn store_pending_rewards<S: Storage>(storage: &mut S, user: &str, amount: Uint128) -> StdResult<()> {
+    bucket(CONFIG_PENDING_REWARDS, storage).save(user.as_bytes(), &amount)
+}
+
+pub fn read_pending_rewards<S: Storage>(storage: &S, user: &str) -> Uint128 {
+    match bucket_read(CONFIG_PENDING_REWARDS, storage).load(user.as_bytes()) {
+        Ok(v) => v,
+        _ => Uint128(0),
+    }
+}
+
+pub fn store_raffle<S: Storage>(storage: &mut S, raffle: &str, result: &RaffleResult) -> StdResult<()> {
+    bucket(CONFIG_RAFFLES, storage).save(raffle.as_bytes(), result)
 }
 
-pub fn read_url_info<S: Storage>(storage: &S, user: &str) -> Option<Vec<String>> {
-    match bucket_read(CONFIG_URL, storage).load(user.as_bytes()) {
+pub fn read_raffle<S: Storage>(storage: &S, raffle: &str) -> Option<RaffleResult> {
+    bucket_read(CONFIG_RAFFLES, storage).may_load(raffle.as_bytes()).unwrap_or_default()
+}
+
+/// prefix of the raffle member lists, tier names may not start with it
+pub const RAFFLE_PREFIX: &str = "raffle/";
+
+/// Member list of a raffle's entrants, apart from the whitelist and tier lists.
+pub fn raffle_list(raffle: &str) -> String {
+    format!("{}{}", RAFFLE_PREFIX, raffle)
+}
+
+/// rounding remainder of reward splits that no wallet received yet
+pub fn store_dust<S: Storage>(storage: &mut S, amount: Uint128) -> StdResult<()> {
+    singleton(storage, CONFIG_DUST).save(&amount)
+}
+
+pub fn read_dust<S: Storage>(storage: &S) -> Uint128 {
+    match singleton_read(storage, CONFIG_DUST).load() {
+        Ok(v) => v,
+        _ => Uint128(0),
+    }
+}
+
+pub fn save_prng_seed<S: Storage>(storage: &mut S) -> Singleton<S, Vec<u8>> {
+    singleton(storage, CONFIG_PRNG_SEED)
+}
+
+pub fn read_prng_seed<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<u8>> {
+    singleton_read(storage, CONFIG_PRNG_SEED)
+}
+
+/// secret seed of the token draw, saved once it matches the commitment
+pub fn save_mint_seed<S: Storage>(storage: &mut S) -> Singleton<S, Vec<u8>> {
+    singleton(storage, CONFIG_MINT_SEED)
+}
+
+pub fn read_mint_seed<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<u8>> {
+    singleton_read(storage, CONFIG_MINT_SEED)
+}
+
+/// rolling entropy every handle mixes into
+pub fn save_entropy_pool<S: Storage>(storage: &mut S) -> Singleton<S, Vec<u8>> {
+    singleton(storage, CONFIG_ENTROPY_POOL)
+}
+
+pub fn read_entropy_pool<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<u8>> {
+    singleton_read(storage, CONFIG_ENTROPY_POOL)
+}
+
+pub fn save_reveal<S: Storage>(storage: &mut S) -> Singleton<S, RevealInfo> {
+    singleton(storage, CONFIG_REVEAL)
+}
+
+pub fn read_reveal<S: Storage>(storage: &S) -> ReadonlySingleton<S, RevealInfo> {
+    singleton_read(storage, CONFIG_REVEAL)
+}
+
+/// hash of a wallet's viewing key
+pub fn store_viewing_key<S: Storage>(storage: &mut S, user: &str, hashed_key: &[u8]) -> StdResult<()> {
+    bucket(CONFIG_VIEWING_KEYS, storage).save(user.as_bytes(), &hashed_key.to_vec())
+}
+
+pub fn read_viewing_key<S: Storage>(storage: &S, user: &str) -> Option<Vec<u8>> {
+    match bucket_read(CONFIG_VIEWING_KEYS, storage).load(user.as_bytes()) {
         Ok(v) => Some(v),
         _ => None,
     }
 }
 
+pub fn revoke_permit<S: Storage>(storage: &mut S, user: &str, permit_name: &str) -> StdResult<()> {
+    bucket(CONFIG_REVOKED_PERMITS, storage).save(&scoped_key(user, permit_name.as_bytes()), &true)
+}
+
+pub fn is_permit_revoked<S: Storage>(storage: &S, user: &str, permit_name: &str) -> bool {
+    match bucket_read::<S, bool>(CONFIG_REVOKED_PERMITS, storage).load(&scoped_key(user, permit_name.as_bytes())) {
+        Ok(v) => v,
+        _ => false,
+    }
+}
+
+/// admin uploaded metadata of a token, keyed by collection and index in it
+pub fn store_token_metadata<S: Storage>(storage: &mut S, collection: &str, index: u32, metadata: &TokenMetadata) -> StdResult<()> {
+    bucket(CONFIG_TOKEN_METADATA, storage).save(&scoped_key(collection, &index.to_be_bytes()), metadata)
+}
+
+pub fn read_token_metadata<S: Storage>(storage: &S, collection: &str, index: u32) -> Option<TokenMetadata> {
+    // entries past the progress are left over from a cleared collection
+    if index >= read_upload_progress(storage, collection) {
+        return None;
+    }
+    bucket_read(CONFIG_TOKEN_METADATA, storage).may_load(&scoped_key(collection, &index.to_be_bytes())).unwrap_or_default()
+}
+
+/// number of leading tokens of a collection that have metadata
+pub fn store_upload_progress<S: Storage>(storage: &mut S, collection: &str, uploaded: u32) -> StdResult<()> {
+    bucket(CONFIG_UPLOAD_PROGRESS, storage).save(collection.as_bytes(), &uploaded)
+}
+
+pub fn read_upload_progress<S: Storage>(storage: &S, collection: &str) -> u32 {
+    bucket_read(CONFIG_UPLOAD_PROGRESS, storage).may_load(collection.as_bytes()).unwrap_or_default().unwrap_or_default()
+}
+
+/// Drops the uploaded metadata of a collection by resetting its progress, so
+/// the cost does not grow with the supply. Reads stop at the progress and
+/// uploads can not skip ahead of it, so the old entries are never seen again.
+pub fn clear_token_metadata<S: Storage>(storage: &mut S, collection: &str) {
+    bucket::<S, u32>(CONFIG_UPLOAD_PROGRESS, storage).remove(collection.as_bytes())
+}
+
+/// The pool of unminted token indices used for swap-and-pop draws. A position
+/// that was never written holds its own index, so the pool needs no setup.
+pub fn store_pool_index<S: Storage>(storage: &mut S, position: u32, index: u32) -> StdResult<()> {
+    bucket(CONFIG_POOL, storage).save(&position.to_be_bytes(), &index)
+}
+
+pub fn read_pool_index<S: Storage>(storage: &S, position: u32) -> Option<u32> {
+    match bucket_read(CONFIG_POOL, storage).load(&position.to_be_bytes()) {
+        Ok(v) => Some(v),
+        _ => None,
+    }
+}
+
+pub fn remove_pool_index<S: Storage>(storage: &mut S, position: u32) {
+    bucket::<S, u32>(CONFIG_POOL, storage).remove(&position.to_be_bytes())
+}
+
+pub fn read_minted_page<S: Storage>(storage: &S, page: u32) -> Vec<u8> {
+    match bucket_read::<S, Binary>(CONFIG_MINTED, storage).load(&page.to_be_bytes()) {
+        Ok(v) => v.0,
+        _ => vec![0u8; (MINTED_PAGE_BITS / 8) as usize],
+    }
+}
+
+pub fn set_minted<S: Storage>(storage: &mut S, index: u32) -> StdResult<()> {
+    let page = index / MINTED_PAGE_BITS;
+    let bit = index % MINTED_PAGE_BITS;
+    let mut bits = read_minted_page(storage, page);
+    bits[(bit / 8) as usize] |= 1 << (bit % 8);
+    bucket(CONFIG_MINTED, storage).save(&page.to_be_bytes(), &Binary(bits))
+}
+
+pub fn is_minted<S: Storage>(storage: &S, index: u32) -> bool {
+    let bit = index % MINTED_PAGE_BITS;
+    let bits = read_minted_page(storage, index / MINTED_PAGE_BITS);
+    bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+}
@@ -0,0 +1,133 @@
+use cosmwasm_std::StdResult;
+
+use crate::error::ContractError;
+
+/// token id of the built-in layout, e.g. "SteamPunk_Human.70"
+pub const DEFAULT_TOKEN_ID: &str = "{prefix}.{index}";
+/// metadata and image file of the built-in layout, e.g. "human/SteamPunk_Human_70.json"
+pub const DEFAULT_METADATA: &str = "{base}/{prefix}_{index}.json";
+pub const DEFAULT_IMAGE: &str = "{base}/{prefix}_{index}.png";
+
+/// widest zero padding `{index:0N}` accepts
+pub const MAX_INDEX_WIDTH: usize = 20;
+
+#[derive(Debug, PartialEq)]
+enum Part<'a> {
+    Literal(&'a str),
+    /// the collection name
+    Prefix,
+    /// the base uri the template is rendered against
+    Base,
+    /// the index inside the collection, zero padded to `width` digits
+    Index { width: usize },
+}
+
+fn invalid(template: &str) -> ContractError {
+    ContractError::InvalidTemplate { template: template.to_string() }
+}
+
+/// Splits a template into literals and `{prefix}`, `{base}`, `{index}` or
+/// `{index:0N}` placeholders.
+fn parse(template: &str) -> Result<Vec<Part<'_>>, ContractError> {
+    let mut parts = vec![];
+    let mut rest = template;
+    while !rest.is_empty() {
+        match rest.find(|c| c == '{' || c == '}') {
+            None => {
+                parts.push(Part::Literal(rest));
+                rest = "";
+            }
+            Some(open) => {
+                if !rest[open..].starts_with('{') {
+                    return Err(invalid(template));
+                }
+                if open > 0 {
+                    parts.push(Part::Literal(&rest[..open]));
+                }
+                let close = match rest[open..].find('}') {
+                    Some(close) => open + close,
+                    None => return Err(invalid(template)),
+                };
+                parts.push(match &rest[open + 1..close] {
+                    "prefix" => Part::Prefix,
+                    "base" => Part::Base,
+                    "index" => Part::Index { width: 0 },
+                    name => {
+                        let width = name
+                            .strip_prefix("index:0")
+                            .and_then(|width| width.parse::<usize>().ok())
+                            .filter(|width| *width > 0 && *width <= MAX_INDEX_WIDTH);
+                        match width {
+                            Some(width) => Part::Index { width },
+                            None => return Err(invalid(template)),
+                        }
+                    }
+                });
+                rest = &rest[close + 1..];
+            }
+        }
+    }
+    Ok(parts)
+}
+
+/// Checks a metadata or image template at configuration time.
+pub fn validate(template: &str) -> StdResult<()> {
+    parse(template)?;
+    Ok(())
+}
+
+/// Checks a token id template. Without the index every token of a collection
+/// would get the same id, without the prefix two collections could. The two
+/// may not touch either: "A" + 10 and "A1" + 0 would both render "A10".
+pub fn validate_token_id(template: &str) -> StdResult<()> {
+    let parts = parse(template)?;
+    if !parts.iter().any(|part| matches!(part, Part::Index { .. })) || !parts.contains(&Part::Prefix) {
+        return Err(invalid(template).into());
+    }
+    let touching = parts
+        .windows(2)
+        .any(|pair| matches!(pair, [Part::Prefix, Part::Index { .. }] | [Part::Index { .. }, Part::Prefix]));
+    if touching {
+        return Err(invalid(template).into());
+    }
+    Ok(())
+}
+
+pub fn render(template: &str, prefix: &str, base: &str, index: u128) -> StdResult<String> {
+    let mut rendered = String::new();
+    for part in parse(template)? {
+        match part {
+            Part::Literal(literal) => rendered.push_str(literal),
+            Part::Prefix => rendered.push_str(prefix),
+            Part::Base => rendered.push_str(base),
+            Part::Index { width } => rendered.push_str(&format!("{:0width$}", index, width = width)),
+        }
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates() {
+        assert_eq!(render(DEFAULT_TOKEN_ID, "SteamPunk_Human", "", 70).unwrap(), "SteamPunk_Human.70");
+        assert_eq!(render(DEFAULT_METADATA, "SteamPunk_Human", "human", 70).unwrap(), "human/SteamPunk_Human_70.json");
+        assert_eq!(render("{prefix}#{index:04}", "Bull", "", 7).unwrap(), "Bull#0007");
+        assert_eq!(render("{base}/{index:02}.json", "Bull", "ipfs://cid", 123).unwrap(), "ipfs://cid/123.json");
+
+        assert!(validate("{base}/{index}.json").is_ok());
+        assert!(validate("{base}/cover.png").is_ok());
+        assert!(validate_token_id(DEFAULT_TOKEN_ID).is_ok());
+        assert!(validate_token_id("{prefix}#{index:04}").is_ok());
+        assert!(validate_token_id("{prefix}").is_err());
+        assert!(validate_token_id("{index:04}").is_err());
+        assert!(validate_token_id("{prefix}{index}").is_err());
+        assert!(validate_token_id("{index:04}{prefix}").is_err());
+        assert!(validate_token_id("{prefix}-{index}").is_ok());
+        for template in ["{name}", "{index", "index}", "{index:4}", "{index:00}", "{index:021}", "{}"].iter() {
+            assert!(validate(template).is_err(), "{}", template);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::Env;
+
+use crate::contract::new_entropy;
+use crate::rand::sha_256;
+
+pub const VIEWING_KEY_SIZE: usize = 32;
+pub const VIEWING_KEY_PREFIX: &str = "api_key_";
+
+/// SNIP-20 style viewing key. Only its sha256 hash is kept in storage.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct ViewingKey(pub String);
+
+impl ViewingKey {
+    /// Derives a fresh key from the contract's prng seed, the block, the
+    /// sender and the entropy the sender supplied.
+    pub fn new(env: &Env, seed: &[u8], entropy: &[u8]) -> Self {
+        let key = new_entropy(env, seed, entropy);
+        ViewingKey(VIEWING_KEY_PREFIX.to_string() + &base64::encode(key))
+    }
+
+    pub fn to_hashed(&self) -> [u8; VIEWING_KEY_SIZE] {
+        sha_256(self.0.as_bytes())
+    }
+
+    /// Compares in constant time so the check does not leak how many bytes
+    /// of the hash matched.
+    pub fn check_viewing_key(&self, hashed: &[u8]) -> bool {
+        let mine = self.to_hashed();
+        hashed.len() == mine.len()
+            && mine.iter().zip(hashed).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_env;
+
+    #[test]
+    fn test_viewing_key() {
+        let env = mock_env("alice", &[]);
+        let key = ViewingKey::new(&env, b"seed", b"entropy");
+        assert!(key.0.starts_with(VIEWING_KEY_PREFIX));
+        assert_ne!(key, ViewingKey::new(&env, b"seed", b"other entropy"));
+
+        let hashed = key.to_hashed();
+        assert!(key.check_viewing_key(&hashed));
+        assert!(!ViewingKey("wrong".to_string()).check_viewing_key(&hashed));
+        assert!(!key.check_viewing_key(&hashed[..31]));
+    }
+}